@@ -1,23 +1,27 @@
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::env::consts::EXE_EXTENSION;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicU32, AtomicU64};
+use std::sync::Mutex;
+use std::time::Duration;
 use std::{env, fs};
 
-use anyhow::{bail, Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
 use once_cell::sync::Lazy;
 use tempfile::NamedTempFile;
 
 use crate::config::Config;
-use crate::consts::VENV_BIN;
+use crate::consts::{PORTABLE_MARKER, SHIMS_VERSION_STAMP, VENV_BIN};
 use crate::platform::{
     get_app_dir, get_canonical_py_path, get_toolchain_python_bin, list_known_toolchains,
     symlinks_supported,
 };
+use crate::pyproject::latest_available_python_version;
 use crate::sources::{get_download_url, PythonVersion, PythonVersionRequest};
 use crate::utils::{
     check_checksum, get_venv_python_bin, set_proxy_variables, symlink_file, unpack_archive,
@@ -37,6 +41,14 @@ pub const SELF_PYTHON_TARGET_VERSION: PythonVersionRequest = PythonVersionReques
 
 const SELF_VERSION: u64 = 4;
 
+const USER_AGENT: &str = concat!("rye/", env!("CARGO_PKG_VERSION"));
+
+/// Downloads smaller than this are not split into ranges.
+const PARALLEL_DOWNLOAD_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// The number of ranges that are downloaded in parallel.
+const PARALLEL_DOWNLOAD_CHUNKS: u64 = 4;
+
 const SELF_REQUIREMENTS: &str = r#"
 build==0.10.0
 certifi==2022.12.7
@@ -59,6 +71,18 @@ virtualenv==20.22.0
 
 static FORCED_TO_UPDATE: AtomicBool = AtomicBool::new(false);
 
+/// How often a download is retried after a transient failure.
+static DOWNLOAD_RETRIES: AtomicU32 = AtomicU32::new(3);
+
+/// Seconds after which connecting or a stalled download is aborted.
+static DOWNLOAD_TIMEOUT: AtomicU64 = AtomicU64::new(30);
+
+/// Whether progress is reported as JSON lines on stderr.
+static JSON_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Set when ctrl-c was pressed while an [`InterruptGuard`] was alive.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 fn is_up_to_date() -> bool {
     static UP_TO_UPDATE: Lazy<bool> = Lazy::new(|| {
         fs::read_to_string(get_app_dir().join("self").join("tool-version.txt"))
@@ -68,6 +92,11 @@ fn is_up_to_date() -> bool {
     *UP_TO_UPDATE || FORCED_TO_UPDATE.load(atomic::Ordering::Relaxed)
 }
 
+/// Returns `true` if the venv for rye itself exists and does not need a refresh.
+pub fn is_self_venv_current() -> bool {
+    get_app_dir().join("self").is_dir() && is_up_to_date()
+}
+
 /// Bootstraps the venv for rye itself
 pub fn ensure_self_venv(output: CommandOutput) -> Result<PathBuf, Error> {
     let app_dir = get_app_dir();
@@ -137,6 +166,15 @@ pub fn ensure_self_venv(output: CommandOutput) -> Result<PathBuf, Error> {
     Ok(venv_dir)
 }
 
+/// Returns the Python version of the self venv if it was bootstrapped.
+pub fn get_self_venv_python_version() -> Option<String> {
+    let cfg = fs::read_to_string(get_app_dir().join("self").join("pyvenv.cfg")).ok()?;
+    cfg.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        matches!(key.trim(), "version" | "version_info").then(|| value.trim().to_string())
+    })
+}
+
 fn do_update(output: CommandOutput, venv_dir: &Path, app_dir: &Path) -> Result<(), Error> {
     if output != CommandOutput::Quiet {
         echo!("Upgrading pip");
@@ -205,6 +243,10 @@ fn do_update(output: CommandOutput, venv_dir: &Path, app_dir: &Path) -> Result<(
 }
 
 pub fn update_core_shims(shims: &Path, this: &Path) -> Result<(), Error> {
+    if shims.join(PORTABLE_MARKER).is_file() {
+        return update_core_shims_relative(shims, this);
+    }
+
     #[cfg(unix)]
     {
         // on linux we cannot symlink at all, as this will misreport.  We will try to do
@@ -254,6 +296,83 @@ pub fn update_core_shims(shims: &Path, this: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the paths of the shims [`update_core_shims`] places in a shims folder.
+pub fn get_core_shim_paths(shims: &Path) -> Vec<PathBuf> {
+    let portable = shims.join(PORTABLE_MARKER).is_file();
+    let names: &[&str] = if cfg!(windows) {
+        &["python", "python3", "pythonw"]
+    } else {
+        &["python", "python3"]
+    };
+    names
+        .iter()
+        .map(|name| match (cfg!(windows), portable) {
+            (true, true) => shims.join(name).with_extension("cmd"),
+            (true, false) => shims.join(name).with_extension("exe"),
+            (false, _) => shims.join(name),
+        })
+        .collect()
+}
+
+/// Returns the version of rye the core shims were last pointed to.
+pub fn read_shims_version(shims: &Path) -> Option<String> {
+    fs::read_to_string(shims.join(SHIMS_VERSION_STAMP))
+        .ok()
+        .map(|x| x.trim().to_string())
+}
+
+/// Records the version of rye the core shims point to.
+pub fn write_shims_version(shims: &Path, version: &str) -> Result<(), Error> {
+    fs::write(shims.join(SHIMS_VERSION_STAMP), version).context("could not write shims version")
+}
+
+/// Writes shim wrappers that locate the sibling `rye` executable at runtime.
+///
+/// Unlike links these do not refer to absolute paths, so the shims folder can be
+/// moved freely.  The wrapper passes the name of the shim via `RYE_SHIM`.
+pub fn update_core_shims_relative(shims: &Path, this: &Path) -> Result<(), Error> {
+    let rye_name = this
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or_else(|| anyhow!("invalid rye executable name"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        for name in ["python", "python3"] {
+            let path = shims.join(name);
+            fs::remove_file(&path).ok();
+            fs::write(
+                &path,
+                format!(
+                    "#!/bin/sh\nRYE_SHIM={} exec \"$(dirname \"$0\")/{}\" \"$@\"\n",
+                    name, rye_name
+                ),
+            )
+            .with_context(|| format!("tried to write {} shim", name))?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("tried to make {} shim executable", name))?;
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        for name in ["python", "python3", "pythonw"] {
+            fs::remove_file(shims.join(name).with_extension("exe")).ok();
+            fs::write(
+                shims.join(name).with_extension("cmd"),
+                format!(
+                    "@echo off\r\nsetlocal\r\nset RYE_SHIM={}.exe\r\n\"%~dp0{}\" %*\r\n",
+                    name, rye_name
+                ),
+            )
+            .with_context(|| format!("tried to write {} shim", name))?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Returns the pip runner for the self venv
 pub fn get_pip_runner(venv: &Path) -> Result<PathBuf, Error> {
     Ok(get_pip_module(venv)?.join("__pip-runner__.py"))
@@ -306,6 +425,25 @@ pub fn is_self_compatible_toolchain(version: &PythonVersion) -> bool {
 }
 
 fn ensure_self_toolchain(output: CommandOutput) -> Result<PythonVersion, Error> {
+    if let Some(request) = Config::current().self_python()? {
+        let version = match latest_available_python_version(&request) {
+            Some(version) => version,
+            None => bail!("configured self python {} is not available", request),
+        };
+        if !is_self_compatible_toolchain(&version) {
+            bail!(
+                "configured self python {} is not compatible for internal use \
+                 (only cpython 3.9 to 3.11 are supported)",
+                version
+            );
+        }
+        echo!(
+            "Using configured python version: {}",
+            style(&version).cyan()
+        );
+        return fetch(&version.into(), output);
+    }
+
     let possible_versions = list_known_toolchains()?
         .into_iter()
         .map(|x| x.0)
@@ -363,17 +501,26 @@ pub fn fetch(
     if output != CommandOutput::Quiet {
         echo!("{} {}", style("Downloading").cyan(), version);
     }
-    let archive_buffer = download_url(url, output)?;
+    // an interrupted download is continued from the partial download next to
+    // the target folder.  It's only removed once the download was verified.
+    let mut part = target_dir.clone().into_os_string();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let archive_buffer = download_url_parallel(url, &part, output)?;
 
     if let Some(sha256) = sha256 {
         if output != CommandOutput::Quiet {
             echo!("{}", style("Checking checksum").cyan());
         }
-        check_checksum(&archive_buffer, sha256)
-            .with_context(|| format!("hash check of {} failed", &url))?;
+        if let Err(err) = check_checksum(&archive_buffer, sha256) {
+            // the partial download is broken, start over next time
+            fs::remove_dir_all(&part).ok();
+            return Err(err.context(format!("hash check of {} failed", &url)));
+        }
     } else if output != CommandOutput::Quiet {
         echo!("Checksum check skipped (no hash available)");
     }
+    fs::remove_dir_all(&part).ok();
 
     unpack_archive(&archive_buffer, &target_dir, 1)
         .with_context(|| format!("unpacking of downloaded tarball {} failed", &url))?;
@@ -392,34 +539,262 @@ pub fn download_url(url: &str, output: CommandOutput) -> Result<Vec<u8>, Error>
     }
 }
 
+/// Changes how often downloads are retried after a transient failure.
+pub fn set_download_retries(retries: u32) {
+    DOWNLOAD_RETRIES.store(retries, atomic::Ordering::Relaxed);
+}
+
+/// Changes the connect and read timeout of downloads in seconds.
+pub fn set_download_timeout(seconds: u64) {
+    DOWNLOAD_TIMEOUT.store(seconds, atomic::Ordering::Relaxed);
+}
+
+/// Reports progress as JSON lines on stderr instead of progress bars.
+pub fn set_json_progress(enabled: bool) {
+    JSON_PROGRESS.store(enabled, atomic::Ordering::Relaxed);
+}
+
+/// Returns `true` if progress is reported as JSON lines.
+pub fn json_progress_enabled() -> bool {
+    JSON_PROGRESS.load(atomic::Ordering::Relaxed)
+}
+
+/// Defers ctrl-c while it's alive.
+///
+/// Instead of killing the process, ctrl-c only sets a flag that downloads
+/// check to abort with an error.  This way temporary files are cleaned up
+/// and an executable is never replaced halfway.  Guards nest, the handler is
+/// installed by the first one and removed again by the last one.
+pub struct InterruptGuard(());
+
+/// The number of live [`InterruptGuard`]s and the handler they replaced.
+#[derive(Default)]
+struct InterruptGuards {
+    count: usize,
+    #[cfg(unix)]
+    old_action: Option<nix::sys::signal::SigAction>,
+}
+
+static INTERRUPT_GUARDS: Lazy<Mutex<InterruptGuards>> = Lazy::new(Default::default);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: std::os::raw::c_int) {
+    INTERRUPTED.store(true, atomic::Ordering::SeqCst);
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_interrupt(ctrl_type: winapi::shared::minwindef::DWORD) -> i32 {
+    // 0 is CTRL_C_EVENT and 1 is CTRL_BREAK_EVENT, closing the console is
+    // left to the default handler.
+    if ctrl_type <= 1 {
+        INTERRUPTED.store(true, atomic::Ordering::SeqCst);
+        1
+    } else {
+        0
+    }
+}
+
+/// Installs an [`InterruptGuard`].
+///
+/// If the handler cannot be installed, ctrl-c keeps its default behavior.
+pub fn catch_interrupts() -> InterruptGuard {
+    let mut guards = INTERRUPT_GUARDS.lock().unwrap();
+    guards.count += 1;
+    if guards.count == 1 {
+        #[cfg(unix)]
+        {
+            use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+            let action = SigAction::new(
+                SigHandler::Handler(on_interrupt),
+                SaFlags::SA_RESTART,
+                SigSet::empty(),
+            );
+            guards.old_action = unsafe { sigaction(Signal::SIGINT, &action) }.ok();
+        }
+        #[cfg(windows)]
+        {
+            unsafe {
+                winapi::um::consoleapi::SetConsoleCtrlHandler(Some(on_interrupt), 1);
+            }
+        }
+    }
+    InterruptGuard(())
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        let mut guards = INTERRUPT_GUARDS.lock().unwrap();
+        guards.count -= 1;
+        if guards.count > 0 {
+            return;
+        }
+        #[cfg(unix)]
+        {
+            if let Some(old_action) = guards.old_action.take() {
+                unsafe {
+                    nix::sys::signal::sigaction(nix::sys::signal::Signal::SIGINT, &old_action).ok();
+                }
+            }
+        }
+        #[cfg(windows)]
+        {
+            unsafe {
+                winapi::um::consoleapi::SetConsoleCtrlHandler(Some(on_interrupt), 0);
+            }
+        }
+        // a later guard starts out uninterrupted
+        INTERRUPTED.store(false, atomic::Ordering::SeqCst);
+    }
+}
+
+/// Returns `true` if ctrl-c was pressed while an [`InterruptGuard`] was alive.
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(atomic::Ordering::SeqCst)
+}
+
+/// Reports the progress of a phase as JSON lines on stderr.
+///
+/// Nothing is reported unless enabled with [`set_json_progress`].  To not
+/// flood the reader an event is only emitted for every percent of progress.
+#[derive(Debug)]
+pub struct JsonProgress {
+    phase: &'static str,
+    total: u64,
+    reported: Option<u64>,
+}
+
+impl JsonProgress {
+    pub fn new(phase: &'static str, total: u64) -> JsonProgress {
+        JsonProgress {
+            phase,
+            total,
+            reported: None,
+        }
+    }
+
+    pub fn set_position(&mut self, done: u64) {
+        if !json_progress_enabled() {
+            return;
+        }
+        let step = (self.total / 100).max(1);
+        if self.reported.map_or(true, |last| {
+            done >= last.saturating_add(step) || (done == self.total && done != last)
+        }) {
+            self.reported = Some(done);
+            eprintln!(
+                "{}",
+                serde_json::json!({
+                    "phase": self.phase,
+                    "bytes_done": done,
+                    "bytes_total": self.total,
+                })
+            );
+        }
+    }
+}
+
 pub fn download_url_ignore_404(url: &str, output: CommandOutput) -> Result<Option<Vec<u8>>, Error> {
     // for now we only allow HTTPS downloads.
     if !url.starts_with("https://") {
         bail!("Refusing insecure download");
     }
 
-    let config = Config::current();
+    // connection problems and server errors are retried with an exponential
+    // backoff.  A 404 is a valid answer and never retried.
+    let _interrupt_guard = catch_interrupts();
+    let retries = DOWNLOAD_RETRIES.load(atomic::Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        let err = match download_url_attempt(url, output) {
+            Ok((404, _)) => return Ok(None),
+            Ok((code, _)) if (500..600).contains(&code) && attempt < retries => {
+                anyhow!("server responded with {}", code)
+            }
+            Ok((code, _)) if !(200..300).contains(&code) => {
+                bail!("Failed to download: {}", code)
+            }
+            Ok((_, archive_buffer)) => return Ok(Some(archive_buffer)),
+            Err(_) if was_interrupted() => bail!("download of {} interrupted", redact_url(url)),
+            Err(err) if is_transient_error(&err) && attempt < retries => err,
+            Err(err) => return Err(download_error(err, url)),
+        };
+        let delay = Duration::from_secs(1 << attempt.min(5));
+        attempt += 1;
+        if output != CommandOutput::Quiet {
+            warn!(
+                "download of {} failed ({}), retrying in {}s ({}/{})",
+                redact_url(url),
+                err,
+                delay.as_secs(),
+                attempt,
+                retries
+            );
+        }
+        std::thread::sleep(delay);
+    }
+}
+
+/// Adds the failed URL to a download error.
+fn download_error(err: Error, url: &str) -> Error {
+    let url = redact_url(url);
+    if err
+        .downcast_ref::<curl::Error>()
+        .map_or(false, |x| x.is_operation_timedout())
+    {
+        let timeout = DOWNLOAD_TIMEOUT.load(atomic::Ordering::Relaxed);
+        err.context(format!("download of {} timed out after {}s", url, timeout))
+    } else {
+        err.context(format!("download of {} failed", url))
+    }
+}
+
+/// Checks if a download error is worth retrying.
+fn is_transient_error(err: &Error) -> bool {
+    let err = match err.downcast_ref::<curl::Error>() {
+        Some(err) => err,
+        None => return false,
+    };
+    err.is_couldnt_connect()
+        || err.is_couldnt_resolve_host()
+        || err.is_operation_timedout()
+        || err.is_recv_error()
+        || err.is_send_error()
+        || err.is_got_nothing()
+        || err.is_partial_file()
+}
+
+/// Performs a single download and returns the response code and body.
+fn download_url_attempt(url: &str, output: CommandOutput) -> Result<(u32, Vec<u8>), Error> {
     let mut archive_buffer = Vec::new();
-    let mut handle = curl::easy::Easy::new();
-    handle.url(url)?;
+    let mut handle = new_curl_handle(url)?;
     handle.progress(true)?;
     handle.follow_location(true)?;
 
-    // we only do https requests here, so we always set an https proxy
-    if let Some(proxy) = config.https_proxy_url() {
-        handle.proxy(&proxy)?;
-    }
-
     let write_archive = &mut archive_buffer;
     {
         let mut transfer = handle.transfer();
         let mut pb = None;
+        let mut json_progress: Option<JsonProgress> = None;
         transfer.progress_function(move |a, b, _, _| {
-            if output == CommandOutput::Quiet {
+            // returning false aborts the transfer
+            if was_interrupted() {
+                return false;
+            }
+            let (down_len, down_pos) = (a as u64, b as u64);
+            if down_len > 0 {
+                // the length changes if a redirect was followed
+                if json_progress.as_ref().map_or(true, |x| x.total != down_len) {
+                    json_progress = Some(JsonProgress::new("download", down_len));
+                }
+                if let Some(ref mut json_progress) = json_progress {
+                    json_progress.set_position(down_pos);
+                }
+            }
+
+            if output == CommandOutput::Quiet || json_progress_enabled() {
                 return true;
             }
 
-            let (down_len, down_pos) = (a as u64, b as u64);
             if down_len > 0 {
                 if down_pos < down_len {
                     if pb.is_none() {
@@ -441,17 +816,397 @@ pub fn download_url_ignore_404(url: &str, output: CommandOutput) -> Result<Optio
             write_archive.write_all(data).unwrap();
             Ok(data.len())
         })?;
+        transfer.perform()?;
+    }
+    Ok((handle.response_code()?, archive_buffer))
+}
+
+/// Downloads a URL in parallel ranges if the server supports it.
+///
+/// The ranges are stored in the `part` folder while downloading so that an
+/// interrupted download continues where it left off.  The caller is expected
+/// to remove the folder once the download was verified.  If the server does
+/// not support range requests this falls back to a regular download.
+pub fn download_url_parallel(
+    url: &str,
+    part: &Path,
+    output: CommandOutput,
+) -> Result<Vec<u8>, Error> {
+    match probe_range_support(url) {
+        Some(len) => download_ranges(url, len, part, output),
+        None => download_url(url, output),
+    }
+}
+
+/// Returns the content length if the server supports range requests.
+fn probe_range_support(url: &str) -> Option<u64> {
+    if !url.starts_with("https://") {
+        return None;
+    }
+
+    let mut handle = new_curl_handle(url).ok()?;
+    handle.nobody(true).ok()?;
+    handle.follow_location(true).ok()?;
+
+    let mut accepts_ranges = false;
+    {
+        let mut transfer = handle.transfer();
         transfer
-            .perform()
-            .with_context(|| format!("download of {} failed", &url))?;
+            .header_function(|header| {
+                let header = String::from_utf8_lossy(header);
+                // headers of redirects are reported too, only the last response counts
+                if header.starts_with("HTTP/") {
+                    accepts_ranges = false;
+                } else if let Some((name, value)) = header.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("accept-ranges")
+                        && value.trim().eq_ignore_ascii_case("bytes")
+                    {
+                        accepts_ranges = true;
+                    }
+                }
+                true
+            })
+            .ok()?;
+        transfer.perform().ok()?;
+    }
+
+    let code = handle.response_code().ok()?;
+    let len = handle.content_length_download().ok()?;
+    if accepts_ranges && (200..300).contains(&code) && len > 0.0 {
+        Some(len as u64)
+    } else {
+        None
+    }
+}
+
+fn download_ranges(
+    url: &str,
+    len: u64,
+    part: &Path,
+    output: CommandOutput,
+) -> Result<Vec<u8>, Error> {
+    // ranges of a previous attempt are only reused for the same download
+    let part_info = format!("{}\n{}\n", url, len);
+    if fs::read_to_string(part.join("info")).ok().as_deref() != Some(part_info.as_str()) {
+        if part.is_dir() {
+            fs::remove_dir_all(part).context("could not remove partial download")?;
+        }
+        fs::create_dir_all(part).context("could not create partial download folder")?;
+        fs::write(part.join("info"), &part_info)?;
+    }
+
+    // a hidden progress bar still counts the downloaded bytes
+    let pb = if output == CommandOutput::Quiet || json_progress_enabled() {
+        ProgressBar::hidden()
+    } else {
+        let pb = ProgressBar::new(len);
+        pb.set_style(
+            ProgressStyle::with_template("{wide_bar} {bytes:>7}/{total_bytes:7}").unwrap(),
+        );
+        pb
+    };
+
+    let chunk_count = if len >= PARALLEL_DOWNLOAD_THRESHOLD {
+        PARALLEL_DOWNLOAD_CHUNKS
+    } else {
+        1
+    };
+    let chunk_size = len / chunk_count + 1;
+    let json_progress = Mutex::new(JsonProgress::new("download", len));
+    // the ranges downloaded so far are kept to continue the download later
+    let _interrupt_guard = catch_interrupts();
+    let chunks = std::thread::scope(|scope| {
+        let pb = &pb;
+        let json_progress = &json_progress;
+        let handles = (0..chunk_count)
+            .map(|idx| {
+                let start = idx * chunk_size;
+                let end = ((idx + 1) * chunk_size).min(len) - 1;
+                let path = part.join(format!("range-{}", idx));
+                scope.spawn(move || download_range(url, start, end, &path, pb, json_progress))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| anyhow!("download thread panicked"))
+                    .and_then(|x| x)
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    });
+    pb.finish_and_clear();
+    chunks?;
+
+    let mut rv = Vec::with_capacity(len as usize);
+    for idx in 0..chunk_count {
+        rv.extend(fs::read(part.join(format!("range-{}", idx)))?);
+    }
+    if rv.len() as u64 != len {
+        bail!(
+            "Failed to download: expected {} bytes, got {}",
+            len,
+            rv.len()
+        );
+    }
+    Ok(rv)
+}
+
+/// Downloads a range into a file.
+///
+/// Whatever is already in the file is not downloaded again, which is also
+/// used to continue after a transient failure.
+fn download_range(
+    url: &str,
+    start: u64,
+    end: u64,
+    path: &Path,
+    pb: &ProgressBar,
+    json_progress: &Mutex<JsonProgress>,
+) -> Result<(), Error> {
+    let range_len = end - start + 1;
+    let mut have = fs::metadata(path).map_or(0, |x| x.len());
+    if have > range_len {
+        fs::remove_file(path)?;
+        have = 0;
+    }
+    pb.inc(have);
+
+    let retries = DOWNLOAD_RETRIES.load(atomic::Ordering::Relaxed);
+    let mut attempt = 0;
+    while have < range_len {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context("could not open partial download")?;
+        let mut handle = new_curl_handle(url)?;
+        handle.follow_location(true)?;
+        handle.range(&format!("{}-{}", start + have, end))?;
+
+        // the status of the last response (after redirects), only the body
+        // of a partial response may end up in the file.
+        let status = Cell::new(0);
+        let rv = {
+            let mut transfer = handle.transfer();
+            transfer.header_function(|header| {
+                if let Some(code) = parse_status_line(header) {
+                    status.set(code);
+                }
+                true
+            })?;
+            transfer.write_function(|data| {
+                // a short write aborts the transfer
+                if was_interrupted() || status.get() != 206 || file.write_all(data).is_err() {
+                    return Ok(0);
+                }
+                pb.inc(data.len() as u64);
+                json_progress.lock().unwrap().set_position(pb.position());
+                Ok(data.len())
+            })?;
+            transfer.perform()
+        };
+        have = fs::metadata(path).map_or(0, |x| x.len());
+        if was_interrupted() {
+            bail!("download of {} interrupted", redact_url(url));
+        }
+        if status.get() != 0 && status.get() != 206 {
+            bail!(
+                "Failed to download range of {}: {}",
+                redact_url(url),
+                status.get()
+            );
+        }
+
+        match rv {
+            // a truncated body is retried like a transient failure
+            Ok(()) if have < range_len && attempt < retries => {
+                std::thread::sleep(Duration::from_secs(1 << attempt.min(5)));
+                attempt += 1;
+            }
+            Ok(()) if have < range_len => {
+                bail!(
+                    "Failed to download range of {}: response was truncated",
+                    redact_url(url)
+                );
+            }
+            Ok(()) => {}
+            Err(err) if attempt < retries && have < range_len => {
+                let err = Error::from(err);
+                if !is_transient_error(&err) {
+                    return Err(download_error(err, url));
+                }
+                std::thread::sleep(Duration::from_secs(1 << attempt.min(5)));
+                attempt += 1;
+            }
+            Err(err) => return Err(download_error(err.into(), url)),
+        }
+    }
+    Ok(())
+}
+
+/// Creates a curl handle for a URL with the user agent, proxy, CA bundle,
+/// timeout and credentials configured.
+fn new_curl_handle(url: &str) -> Result<curl::easy::Easy, Error> {
+    let mut handle = curl::easy::Easy::new();
+    handle.url(url)?;
+    handle.useragent(USER_AGENT)?;
+    set_curl_proxy(&mut handle)?;
+    set_curl_ca_bundle(&mut handle)?;
+    set_curl_timeout(&mut handle)?;
+    set_curl_auth(&mut handle, url)?;
+    Ok(handle)
+}
+
+/// Returns the status code if a header line is the status line of a response.
+fn parse_status_line(header: &[u8]) -> Option<u32> {
+    let header = std::str::from_utf8(header).ok()?;
+    if !header.starts_with("HTTP/") {
+        return None;
     }
+    header.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Configures the credentials for a download from the release mirror.
+///
+/// Credentials in the userinfo of the URL are picked up by curl itself.
+/// Otherwise `RYE_RELEASE_USER` and `RYE_RELEASE_TOKEN` are sent to URLs of
+/// the mirror in `RYE_RELEASE_URL`.  Curl does not send them on redirects
+/// to other hosts.
+fn set_curl_auth(handle: &mut curl::easy::Easy, url: &str) -> Result<(), Error> {
+    let token = match env::var("RYE_RELEASE_TOKEN") {
+        Ok(token) => token,
+        Err(_) => return Ok(()),
+    };
+    let is_mirror = env::var("RYE_RELEASE_URL").map_or(false, |release_url| {
+        let release_url = release_url.trim_end_matches('/');
+        url.strip_prefix(release_url)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with('/'))
+    });
+    let has_userinfo = url::Url::parse(url).map_or(false, |x| !x.username().is_empty());
+    if is_mirror && !has_userinfo {
+        handle.username(&env::var("RYE_RELEASE_USER").unwrap_or_default())?;
+        handle.password(&token)?;
+    }
+    Ok(())
+}
+
+/// Hides the credentials in a URL so that it can be shown.
+pub fn redact_url(url: &str) -> Cow<'_, str> {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            parsed.set_username("***").ok();
+            parsed.set_password(None).ok();
+            Cow::Owned(parsed.to_string())
+        }
+        _ => Cow::Borrowed(url),
+    }
+}
+
+/// Configures the proxy for a curl handle.
+fn set_curl_proxy(handle: &mut curl::easy::Easy) -> Result<(), Error> {
+    let config = Config::current();
+
+    // we only do https requests here, so we always set an https proxy.  Basic
+    // auth credentials embedded in the proxy URL are picked up by curl.
+    if let Some(proxy) = config.https_proxy_url() {
+        handle.proxy(&proxy)?;
+    }
+    if let Some(no_proxy) = config.no_proxy() {
+        handle.noproxy(&no_proxy)?;
+    }
+    Ok(())
+}
+
+/// Configures the CA bundle for a curl handle.
+///
+/// If `RYE_CA_BUNDLE` points to a PEM file, the certificates in it are trusted
+/// instead of the default roots.  This is needed behind proxies that intercept
+/// TLS with their own CA.
+fn set_curl_ca_bundle(handle: &mut curl::easy::Easy) -> Result<(), Error> {
+    if let Some(path) = env::var_os("RYE_CA_BUNDLE").filter(|x| !x.is_empty()) {
+        let path = Path::new(&path);
+        if !path.is_file() {
+            bail!(
+                "CA bundle {} (from RYE_CA_BUNDLE) does not exist",
+                path.display()
+            );
+        }
+        handle.cainfo(path)?;
+    }
+    Ok(())
+}
+
+/// Configures the timeouts for a curl handle.
+///
+/// There is no limit for the whole transfer as large downloads on slow
+/// connections are fine as long as data keeps coming in.
+fn set_curl_timeout(handle: &mut curl::easy::Easy) -> Result<(), Error> {
+    let timeout = Duration::from_secs(DOWNLOAD_TIMEOUT.load(atomic::Ordering::Relaxed));
+    handle.connect_timeout(timeout)?;
+    handle.low_speed_limit(1)?;
+    handle.low_speed_time(timeout)?;
+    Ok(())
+}
+
+/// Checks if the host toolchains are downloaded from can be reached.
+///
+/// Any response counts, this only detects DNS, connection and proxy problems.
+pub fn check_toolchain_host_reachable() -> Result<(), Error> {
+    let host = match get_download_url(&SELF_PYTHON_TARGET_VERSION)
+        .and_then(|(_, url, _)| url::Url::parse(url).ok())
+    {
+        Some(url) => format!("{}/", url.origin().ascii_serialization()),
+        None => return Ok(()),
+    };
+
+    let mut handle = new_curl_handle(&host)?;
+    handle.nobody(true)?;
+    handle
+        .perform()
+        .with_context(|| format!("could not reach {}", host))?;
+    Ok(())
+}
+
+/// Resolves the final URL after following all redirects.
+pub fn resolve_redirect_url(url: &str) -> Result<String, Error> {
+    // for now we only allow HTTPS requests.
+    if !url.starts_with("https://") {
+        bail!("Refusing insecure request");
+    }
+
+    let mut handle = new_curl_handle(url)?;
+    handle.nobody(true)?;
+    handle.follow_location(true)?;
+
+    handle
+        .perform()
+        .with_context(|| format!("request to {} failed", redact_url(url)))?;
     let code = handle.response_code()?;
-    if code == 404 {
-        Ok(None)
-    } else if !(200..300).contains(&code) {
-        bail!("Failed to download: {}", code)
+    if !(200..300).contains(&code) {
+        bail!("Failed to resolve {}: {}", redact_url(url), code);
+    }
+    Ok(handle.effective_url()?.unwrap_or(url).to_string())
+}
+
+/// Returns the size of a download as reported by a HEAD request.
+pub fn get_content_length(url: &str) -> Option<u64> {
+    if !url.starts_with("https://") {
+        return None;
+    }
+
+    let mut handle = new_curl_handle(url).ok()?;
+    handle.nobody(true).ok()?;
+    handle.follow_location(true).ok()?;
+    handle.perform().ok()?;
+
+    let code = handle.response_code().ok()?;
+    let len = handle.content_length_download().ok()?;
+    if (200..300).contains(&code) && len > 0.0 {
+        Some(len as u64)
     } else {
-        Ok(Some(archive_buffer))
+        None
     }
 }
 
@@ -490,3 +1245,13 @@ fn validate_shared_libraries(py: &Path) -> Result<(), Error> {
         Visit https://rye-up.com/guide/faq/#missing-shared-libraries-on-linux for next steps."
     );
 }
+
+#[test]
+fn test_parse_status_line() {
+    assert_eq!(
+        parse_status_line(b"HTTP/1.1 206 Partial Content\r\n"),
+        Some(206)
+    );
+    assert_eq!(parse_status_line(b"HTTP/2 200\r\n"), Some(200));
+    assert_eq!(parse_status_line(b"content-length: 206\r\n"), None);
+}
@@ -1,7 +1,7 @@
 use std::env;
 
 use anyhow::Error;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 mod add;
 mod build;
@@ -41,6 +41,17 @@ struct Args {
     /// Print the version
     #[arg(long)]
     version: bool,
+    /// Controls when to use colors in the output.
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto")]
+    color: ColorChoice,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum ColorChoice {
+    /// Use colors if the output is a terminal.
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Parser, Debug)]
@@ -84,6 +95,16 @@ pub fn execute() -> Result<(), Error> {
     }
 
     let args = Args::try_parse()?;
+    // auto keeps the terminal detection of console
+    let colors = match args.color {
+        ColorChoice::Auto => None,
+        ColorChoice::Always => Some(true),
+        ColorChoice::Never => Some(false),
+    };
+    if let Some(enabled) = colors {
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
     let cmd = if args.version {
         return print_version();
     } else if let Some(cmd) = args.command {
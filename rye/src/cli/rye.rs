@@ -1,32 +1,77 @@
 use std::borrow::Cow;
-use std::env::consts::{ARCH, EXE_EXTENSION, OS};
+use std::env::consts::{ARCH, EXE_EXTENSION, EXE_SUFFIX, OS};
 use std::env::{join_paths, split_paths};
+use std::io::{BufRead, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::{env, fs};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fmt, fs};
 
-use anyhow::{bail, Context, Error};
-use clap::{CommandFactory, Parser};
+use anyhow::{anyhow, bail, Context, Error};
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum};
 use clap_complete::Shell;
+use clap_complete_nushell::Nushell;
 use console::style;
-use minijinja::render;
+use indicatif::{ProgressBar, ProgressStyle};
+use minijinja::{context, Environment};
+use once_cell::sync::Lazy;
+use pep440_rs::Version;
 use self_replace::self_delete_outside_path;
-use tempfile::tempdir;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 
 use crate::bootstrap::{
-    download_url, download_url_ignore_404, ensure_self_venv, is_self_compatible_toolchain,
-    update_core_shims,
+    catch_interrupts, check_toolchain_host_reachable, download_url, download_url_ignore_404,
+    ensure_self_venv, fetch, get_content_length, get_core_shim_paths, get_self_venv_python_version,
+    is_self_compatible_toolchain, is_self_venv_current, json_progress_enabled, read_shims_version,
+    redact_url, resolve_redirect_url, set_download_retries, set_download_timeout,
+    set_json_progress, update_core_shims, was_interrupted, write_shims_version, JsonProgress,
+};
+use crate::cli::toolchain::{get_binary_archs, register_toolchain, Format};
+use crate::config::Config;
+use crate::consts::PORTABLE_MARKER;
+#[cfg(unix)]
+use crate::consts::SYSTEM_APP_DIR;
+use crate::platform::{get_app_dir, get_xdg_home, symlinks_supported};
+use crate::sources::{iter_downloadable, PythonVersion};
+use crate::sync::get_registered_venvs;
+use crate::tui::suppress_echo;
+use crate::utils::{
+    check_checksum, check_signature, compute_checksum, copy_dir, get_available_space,
+    get_venv_python_bin, lock_app_dir, unpack_archive, CommandOutput, CopyDirOptions, QuietExit,
 };
-use crate::cli::toolchain::register_toolchain;
-use crate::platform::{get_app_dir, symlinks_supported};
-use crate::utils::{check_checksum, CommandOutput, QuietExit};
 
 #[cfg(windows)]
 const DEFAULT_HOME: &str = "%USERPROFILE%\\.rye";
 #[cfg(unix)]
 const DEFAULT_HOME: &str = "$HOME/.rye";
 
-const GITHUB_REPO: &str = "https://github.com/mitsuhiko/rye";
+/// The GitHub repository rye updates itself from.
+///
+/// Forks can change this at build time with the `RYE_UPDATE_REPO` environment
+/// variable (eg: `https://github.com/someone/rye`).
+const GITHUB_REPO: &str = match option_env!("RYE_UPDATE_REPO") {
+    Some(repo) => repo,
+    None => "https://github.com/mitsuhiko/rye",
+};
+/// The magic bytes at the start of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Exit code of `rye self update` if there was nothing to update.
+const UP_TO_DATE_EXIT_CODE: i32 = 3;
+/// Cached release downloads older than this are not reused.
+const RELEASE_CACHE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+/// How long `rye self` operations wait for another one to finish.
+const APP_DIR_LOCK_WAIT: Duration = Duration::from_secs(30);
+/// The minisign public key release signatures are checked against.  This can
+/// be overridden at build time with the `RYE_RELEASE_PUBLIC_KEY` environment
+/// variable (eg: for builds that are released from a fork).
+const RELEASE_PUBLIC_KEY: &str = match option_env!("RYE_RELEASE_PUBLIC_KEY") {
+    Some(key) => key,
+    None => "RWQy/tdIit02B8H0c+B+zL623sQPTD+x1C4dsgR0SnO5AgqS6JwovWjW",
+};
 const UNIX_ENV_FILE: &str = r#"
 # rye shell setup
 {%- if custom_home %}
@@ -42,6 +87,15 @@ esac
 
 "#;
 
+#[cfg(unix)]
+const FISH_REMOVE_USER_PATH: &str = r#"
+if set -l idx (contains -i -- $RYE_SHIMS $fish_user_paths)
+    set -Ue fish_user_paths[$idx]
+else
+    exit 1
+end
+"#;
+
 /// Rye self management
 #[derive(Parser, Debug)]
 pub struct Args {
@@ -54,7 +108,58 @@ pub struct Args {
 pub struct CompletionCommand {
     /// The shell to generate a completion script for (defaults to 'bash').
     #[arg(short, long)]
-    shell: Option<Shell>,
+    shell: Option<ShellType>,
+    /// Write the completion script to this file instead of stdout.
+    #[arg(short, long, conflicts_with = "all")]
+    output: Option<PathBuf>,
+    /// Generate completion scripts for all shells.
+    #[arg(long, requires = "output_dir", conflicts_with = "shell")]
+    all: bool,
+    /// The folder to write the completion scripts to with `--all`.
+    #[arg(long, requires = "all")]
+    output_dir: Option<PathBuf>,
+    /// Print the supported shells one per line and exit.
+    #[arg(long, conflicts_with_all = ["shell", "output", "all"])]
+    list_shells: bool,
+    /// Also complete hidden commands such as `rye self install`.
+    #[arg(long)]
+    show_hidden: bool,
+    /// Emit a small stub that loads the completions on first use.
+    ///
+    /// This keeps the shell startup fast.  Supported for bash, fish and zsh.
+    #[arg(long, conflicts_with_all = ["all", "list_shells"])]
+    lazy: bool,
+    /// The name of the rye binary to complete.
+    ///
+    /// This is needed if rye was installed under a different name.
+    #[arg(long, value_name = "NAME", default_value = "rye")]
+    bin_name: String,
+}
+
+/// The shells that completion scripts can be generated for.
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum ShellType {
+    Bash,
+    Elvish,
+    Fish,
+    #[value(name = "nu", alias = "nushell")]
+    Nushell,
+    Powershell,
+    Zsh,
+}
+
+impl ShellType {
+    /// The conventional filename of the completion script.
+    fn file_name(self) -> &'static str {
+        match self {
+            ShellType::Bash => "rye.bash",
+            ShellType::Elvish => "rye.elv",
+            ShellType::Fish => "rye.fish",
+            ShellType::Nushell => "rye.nu",
+            ShellType::Powershell => "_rye.ps1",
+            ShellType::Zsh => "_rye",
+        }
+    }
 }
 
 /// Performs an update of rye.
@@ -75,6 +180,121 @@ pub struct UpdateCommand {
     /// Force reinstallation
     #[arg(long)]
     force: bool,
+    /// Allow updating to an older major or minor version.
+    #[arg(long)]
+    allow_downgrade: bool,
+    /// Only check if an update is available.
+    ///
+    /// Exits with status 2 if a newer version is available.
+    #[arg(long, conflicts_with_all = ["version", "tag", "rev", "force"])]
+    check: bool,
+    /// List the most recent releases instead of updating.
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "10",
+        conflicts_with_all = ["version", "tag", "rev", "force", "check", "rollback", "format"]
+    )]
+    list: Option<usize>,
+    /// Fail if the release does not come with a valid signature.
+    ///
+    /// With `--from-file` the signature is read from a `.minisig` file next
+    /// to it.
+    #[arg(long, conflicts_with_all = ["tag", "rev"])]
+    require_signature: bool,
+    /// Restore the version that was installed before the last update.
+    #[arg(long, conflicts_with_all = ["version", "tag", "rev", "force", "check"])]
+    rollback: bool,
+    /// The release channel to update from.
+    ///
+    /// The channel is remembered for future updates.
+    #[arg(long, conflicts_with_all = ["version", "tag", "rev", "rollback"])]
+    channel: Option<Channel>,
+    /// Request parseable output format
+    #[arg(long, conflicts_with_all = ["check", "rollback"])]
+    format: Option<Format>,
+    /// Always download the release even if a verified download is cached.
+    ///
+    /// With `--rev` or `--tag` this rebuilds rye even if that rev or tag was
+    /// built before.
+    #[arg(long)]
+    no_cache: bool,
+    /// The proxy to use for downloading the release.
+    #[arg(long)]
+    proxy: Option<String>,
+    /// A PEM file with the CA certificates to trust for downloads.
+    ///
+    /// This can also be set with the `RYE_CA_BUNDLE` environment variable.
+    #[arg(long, value_name = "PATH")]
+    ca_bundle: Option<PathBuf>,
+    /// Download releases from a mirror instead of GitHub.
+    ///
+    /// This can also be set with the `RYE_RELEASE_URL` environment variable.
+    #[arg(long)]
+    release_url: Option<String>,
+    /// How often to retry a download after a transient failure.
+    #[arg(long)]
+    retries: Option<u32>,
+    /// Seconds after which connecting or a stalled download is aborted.
+    ///
+    /// Defaults to 30 seconds, 0 disables the timeout.
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+    /// Print the download URLs.
+    #[arg(short, long, conflicts_with = "format")]
+    verbose: bool,
+    /// Report the progress as JSON lines on stderr instead of progress bars.
+    ///
+    /// Each line has the `phase` (download, decompress or replace) and the
+    /// `bytes_done` and `bytes_total` of it.
+    #[arg(long)]
+    json_progress: bool,
+    /// Update from a release artifact on disk instead of downloading it.
+    #[arg(
+        long,
+        conflicts_with_all = ["version", "tag", "rev", "check", "rollback", "channel"]
+    )]
+    from_file: Option<PathBuf>,
+    /// The expected checksum of the file passed to `--from-file`.
+    #[arg(long, requires = "from_file")]
+    checksum: Option<String>,
+    /// Recreate rye's internals even if they look intact.
+    ///
+    /// Missing internals, for instance after an interrupted installation,
+    /// are always repaired after an update.
+    #[arg(long, conflicts_with_all = ["check", "list", "rollback"])]
+    repair: bool,
+    /// Only update the shims to point to this executable.
+    ///
+    /// Nothing is downloaded and the executable is not replaced.  This is
+    /// useful if the rye executable itself is managed by a package manager.
+    #[arg(
+        long,
+        conflicts_with_all = ["version", "tag", "rev", "check", "list", "rollback", "format", "from_file"]
+    )]
+    shims_only: bool,
+}
+
+/// The release channels rye can be updated from.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+#[value(rename_all = "snake_case")]
+enum Channel {
+    /// Only stable releases.
+    Stable,
+    /// Stable and pre-releases.
+    Prerelease,
+}
+
+/// Release information from the GitHub API.
+#[derive(Deserialize, Debug)]
+struct GithubRelease {
+    tag_name: String,
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    published_at: Option<String>,
 }
 
 /// Triggers the initial installation of Rye.
@@ -83,13 +303,113 @@ pub struct UpdateCommand {
 /// to the intended target location and to add Rye to the environment
 /// variables.
 #[derive(Parser, Debug)]
+#[command(group = ArgGroup::new("toolchain_source").args(["toolchain", "toolchain_url"]))]
 pub struct InstallCommand {
     /// Skip prompts.
     #[arg(short, long)]
     yes: bool,
+    /// Only print errors and the final result.  Implies `--yes`.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Print more details, for instance the contents of the env file.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Install into this folder instead of the rye home.
+    ///
+    /// This takes precedence over the `RYE_HOME` environment variable.
+    #[arg(long, alias = "path")]
+    home: Option<PathBuf>,
     /// Register a specific toolchain before bootstrap.
+    ///
+    /// Pass `-` to read a toolchain archive from stdin instead.
     #[arg(long)]
     toolchain: Option<PathBuf>,
+    /// Download a toolchain archive and register it before bootstrap.
+    #[arg(long)]
+    toolchain_url: Option<String>,
+    /// Architecture of the toolchain if it's not native.
+    #[arg(long, requires = "toolchain_source")]
+    toolchain_arch: Option<String>,
+    /// The expected sha256 checksum of the toolchain.
+    ///
+    /// With `--toolchain-url` this is the checksum of the archive.
+    #[arg(long, requires = "toolchain_source")]
+    toolchain_sha256: Option<String>,
+    /// Register the toolchain even if it's built for another architecture.
+    ///
+    /// This is useful if the toolchain deliberately runs under emulation,
+    /// for instance with Rosetta.
+    #[arg(long, requires = "toolchain_source")]
+    allow_arch_mismatch: bool,
+    /// Do not bootstrap rye internals during installation.
+    ///
+    /// This skips all network access and implies --yes.  The internals are
+    /// instead bootstrapped the first time they are needed.
+    #[arg(long, requires = "toolchain")]
+    offline: bool,
+    /// Do not create the internal virtualenv during installation.
+    ///
+    /// It's created on demand the first time it's needed instead.
+    #[arg(long)]
+    no_self_venv: bool,
+    /// Print the steps of the installation without performing them.
+    #[arg(long)]
+    dry_run: bool,
+    /// Install even if the same or a newer version of rye is already installed.
+    #[arg(long)]
+    force: bool,
+    /// Remove the existing installation before installing.
+    ///
+    /// This removes rye's internals, the shims and the env file.  Toolchains
+    /// are kept unless `--clean` is passed.
+    #[arg(long, conflicts_with = "dry_run")]
+    force_reinstall: bool,
+    /// Also remove the toolchains with `--force-reinstall`.
+    #[arg(long, requires = "force_reinstall")]
+    clean: bool,
+    /// Use relative shims so that the rye home folder can be moved.
+    #[arg(long)]
+    portable: bool,
+    /// Render the env file from this template instead of the default one.
+    ///
+    /// The template has access to `custom_home` and `rye_home`.  It must
+    /// still put the shims folder on the `PATH`.
+    #[arg(long)]
+    env_template: Option<PathBuf>,
+    /// Install the rye executable under a different name, for instance `rye2`.
+    ///
+    /// This allows multiple rye installations side by side.  The name must
+    /// start with `rye`.
+    #[arg(long)]
+    shim_name: Option<String>,
+    /// Do not modify the PATH and do not print instructions about it.
+    #[arg(long)]
+    no_modify_path: bool,
+    /// Confirm the installation when enter is pressed at the prompt.
+    ///
+    /// This can also be enabled by setting `RYE_INSTALL_DEFAULT_YES` to `1`.
+    #[arg(long, conflicts_with = "yes")]
+    default_yes: bool,
+    /// Cancel the installation if a prompt is not answered within this
+    /// many seconds.
+    #[arg(long, value_name = "SECONDS", conflicts_with = "yes")]
+    prompt_timeout: Option<u64>,
+    /// Install rye for all users of this machine.
+    ///
+    /// Unless `--home` is given, rye is installed into `/opt/rye`.  The
+    /// installation is made readable by all users.
+    #[arg(long, conflicts_with = "portable")]
+    system: bool,
+    /// Persist that no usage data may be sent, even if rye starts to collect some.
+    #[arg(long)]
+    no_telemetry: bool,
+    /// Keep the installation as small as possible, for instance in containers.
+    ///
+    /// No completion scripts or other helper files are placed, and updates
+    /// do not keep a backup of the previous version.  The profile is recorded
+    /// in the install manifest.
+    #[arg(long)]
+    minimal: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -97,6 +417,114 @@ enum InstallMode {
     Default,
     NoPrompts,
     AutoInstall,
+    Offline,
+}
+
+/// Controls the behavior of the installer.
+#[derive(Debug, Default)]
+struct InstallOptions<'a> {
+    /// Register this toolchain before bootstrap.
+    toolchain_path: Option<&'a Path>,
+    /// Download a toolchain from here and register it before bootstrap.
+    toolchain_url: Option<&'a str>,
+    /// The architecture of the toolchain.
+    toolchain_arch: Option<&'a str>,
+    /// The expected checksum of the toolchain.
+    toolchain_sha256: Option<&'a str>,
+    /// Skip checking the architecture of the toolchain.
+    allow_arch_mismatch: bool,
+    /// Defer creating the self venv to first use.
+    no_self_venv: bool,
+    /// Only print what would be done.
+    dry_run: bool,
+    /// Skip the check for an already installed version.
+    force: bool,
+    /// Write relative shims.
+    portable: bool,
+    /// Custom template for the env file.
+    env_template: Option<&'a Path>,
+    /// Alternative name of the rye executable.
+    shim_name: Option<&'a str>,
+    /// Leave the PATH alone.
+    no_modify_path: bool,
+    /// Default the confirmation prompt to yes.
+    default_yes: bool,
+    /// Prompts not answered within this time are declined.
+    prompt_timeout: Option<Duration>,
+    /// Install for all users.
+    system: bool,
+    /// Disable telemetry in the config.
+    no_telemetry: bool,
+    /// Install with the minimal profile.
+    minimal: bool,
+    /// How verbose the installer is.
+    output: CommandOutput,
+}
+
+/// Record of what the installer placed, stored as `install-manifest.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct InstallManifest {
+    /// The version of rye that was installed.
+    version: String,
+    /// When the installation happened (seconds since the unix epoch).
+    installed_at: u64,
+    /// The installed rye executable.
+    binary: PathBuf,
+    /// The shims created next to the executable.
+    shims: Vec<PathBuf>,
+    /// The env file for the shell config.
+    env_file: Option<PathBuf>,
+    /// The internal virtualenv.
+    self_venv: Option<PathBuf>,
+    /// The toolchain registered during installation.
+    toolchain: Option<String>,
+    /// Whether rye was installed with `--minimal`.
+    #[serde(default)]
+    minimal: bool,
+}
+
+impl InstallManifest {
+    fn path(app_dir: &Path) -> PathBuf {
+        app_dir.join("install-manifest.json")
+    }
+
+    fn load(app_dir: &Path) -> Option<InstallManifest> {
+        let contents = fs::read(InstallManifest::path(app_dir)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Rewrites all paths from the old to the new home folder.
+    fn relocate(&mut self, old_home: &Path, new_home: &Path) {
+        let rebase = |path: &mut PathBuf| {
+            if let Ok(rest) = path.strip_prefix(old_home) {
+                *path = new_home.join(rest);
+            }
+        };
+        rebase(&mut self.binary);
+        self.shims.iter_mut().for_each(rebase);
+        self.env_file.iter_mut().for_each(rebase);
+        self.self_venv.iter_mut().for_each(rebase);
+    }
+
+    fn save(&self, app_dir: &Path) -> Result<(), Error> {
+        fs::write(
+            InstallManifest::path(app_dir),
+            serde_json::to_string_pretty(self)?,
+        )
+        .context("failed to write install manifest")
+    }
+}
+
+/// Moves the rye installation to a new home folder.
+///
+/// The internal virtualenv is recreated on first use as it cannot be moved.
+#[derive(Parser, Debug)]
+pub struct RelocateCommand {
+    /// The new home folder.
+    new_home: PathBuf,
+    /// Move into the new home folder even if it is not empty.
+    #[arg(long)]
+    force: bool,
 }
 
 /// Uninstalls rye again.
@@ -105,341 +533,3258 @@ pub struct UninstallCommand {
     /// Skip safety check.
     #[arg(short, long)]
     yes: bool,
+    /// Only print errors.  Implies `--yes`.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Do not remove downloaded or registered toolchains.
+    #[arg(long)]
+    keep_toolchains: bool,
+    /// Remove the sourcing of the env file from the shell config.
+    #[arg(long)]
+    clean_path: bool,
+    /// Uninstall from this folder instead of the rye home.
+    ///
+    /// This takes precedence over the `RYE_HOME` environment variable.
+    #[arg(long, alias = "path")]
+    home: Option<PathBuf>,
+    /// Why rye is uninstalled, for instance by provisioning scripts.
+    ///
+    /// The reason is left behind as a comment in the env file and recorded
+    /// in `uninstall-record.json`.
+    #[arg(long)]
+    reason: Option<String>,
+    /// Cancel if a prompt is not answered within this many seconds.
+    #[arg(long, value_name = "SECONDS", conflicts_with = "yes")]
+    prompt_timeout: Option<u64>,
+}
+
+/// Repairs the shims.
+///
+/// This re-creates the shims so that they point to the installed rye executable
+/// again, for instance after the rye home folder was moved.
+#[derive(Parser, Debug)]
+pub struct FixShimsCommand {}
+
+/// Checks that the rye installation works.
+///
+/// Prints a checklist and exits with an error if a critical check fails.
+#[derive(Parser, Debug)]
+pub struct DoctorCommand {}
+
+/// Prints which rye executable is running and which one is installed.
+#[derive(Parser, Debug)]
+pub struct WhichCommand {}
+
+/// Prints the version of rye.
+#[derive(Parser, Debug)]
+pub struct VersionCommand {
+    /// Include build and installation details.
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+/// Lists or prunes the backups made by `rye self update`.
+///
+/// Without arguments the backups are listed.
+#[derive(Parser, Debug)]
+pub struct BackupsCommand {
+    /// List the backups with their versions and sizes.
+    #[arg(long)]
+    list: bool,
+    /// Delete all but the most recent backups.
+    #[arg(long, conflicts_with = "list")]
+    prune: bool,
+    /// How many backups `--prune` keeps.
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "prune")]
+    keep: usize,
+}
+
+/// Prints the shell statements that put rye on the `PATH`.
+///
+/// This is meant to be evaluated by the shell, for instance with
+/// `eval "$(rye self env)"`.
+#[derive(Parser, Debug)]
+pub struct EnvCommand {
+    /// The shell to print the statements for (defaults to 'bash').
+    #[arg(short, long)]
+    shell: Option<ShellType>,
+    /// Print the path of the env file instead.
+    #[arg(long, conflicts_with = "shell")]
+    path: bool,
 }
 
 #[derive(Parser, Debug)]
 enum SubCommand {
+    Backups(BackupsCommand),
     Completion(CompletionCommand),
+    Doctor(DoctorCommand),
+    Env(EnvCommand),
+    FixShims(FixShimsCommand),
     Update(UpdateCommand),
     #[command(hide = true)]
     Install(InstallCommand),
+    Relocate(RelocateCommand),
     Uninstall(UninstallCommand),
+    Version(VersionCommand),
+    Which(WhichCommand),
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
     match cmd.command {
+        SubCommand::Backups(args) => backups(args),
         SubCommand::Completion(args) => completion(args),
+        SubCommand::Doctor(args) => doctor(args),
+        SubCommand::Env(args) => print_env(args),
+        SubCommand::FixShims(args) => fix_shims(args),
         SubCommand::Update(args) => update(args),
         SubCommand::Install(args) => install(args),
+        SubCommand::Relocate(args) => relocate(args),
         SubCommand::Uninstall(args) => uninstall(args),
+        SubCommand::Version(args) => version(args),
+        SubCommand::Which(args) => which(args),
     }
 }
 
 fn completion(args: CompletionCommand) -> Result<(), Error> {
-    clap_complete::generate(
-        args.shell.unwrap_or(Shell::Bash),
-        &mut super::Args::command(),
-        "rye",
-        &mut std::io::stdout(),
-    );
-
-    Ok(())
-}
-
-fn update(args: UpdateCommand) -> Result<(), Error> {
-    // make sure to read the exe before self_replace as otherwise we might read
-    // a bad executable name on Linux where the move is picked up.
-    let current_exe = env::current_exe()?;
-
-    // git based installation with cargo
-    if args.rev.is_some() || args.tag.is_some() {
-        let mut cmd = Command::new("cargo");
-        let tmp = tempdir()?;
-        cmd.arg("install")
-            .arg("--git")
-            .arg("https://github.com/mitsuhiko/rye")
-            .arg("--root")
-            .env(
-                "PATH",
-                join_paths(
-                    Some(tmp.path().join("bin"))
-                        .into_iter()
-                        .chain(split_paths(&env::var_os("PATH").unwrap_or_default())),
-                )?,
-            )
-            .arg(tmp.path());
-        if let Some(ref rev) = args.rev {
-            cmd.arg("--rev");
-            cmd.arg(rev);
-        } else if let Some(ref tag) = args.tag {
-            cmd.arg("--tag");
-            cmd.arg(tag);
-        }
-        if args.force {
-            cmd.arg("--force");
-        }
-        cmd.arg("rye");
-        let status = cmd.status().context("unable to update via cargo-install")?;
-        if !status.success() {
-            bail!("failed to self-update via cargo-install");
-        }
-        update_exe_and_shims(
-            &tmp.path()
-                .join("bin")
-                .join("rye")
-                .with_extension(EXE_EXTENSION),
-        )?;
-    } else {
-        let version = args.version.as_deref().unwrap_or("latest");
-        echo!("Updating to {version}");
-        let binary = format!("rye-{ARCH}-{OS}");
-        let ext = if cfg!(unix) { ".gz" } else { ".exe" };
-        let url = if version == "latest" {
-            format!("{GITHUB_REPO}/releases/latest/download/{binary}{ext}")
-        } else {
-            format!("{GITHUB_REPO}/releases/download/{version}/{binary}{ext}")
-        };
-        let sha256_url = format!("{}.sha256", url);
-        let bytes = download_url(&url, CommandOutput::Normal)
-            .with_context(|| format!("could not download release {version} for this platform"))?;
-        if let Some(sha256_bytes) = download_url_ignore_404(&sha256_url, CommandOutput::Normal)? {
-            let checksum = String::from_utf8_lossy(&sha256_bytes);
-            echo!("Checking checksum");
-            check_checksum(&bytes, checksum.trim())
-                .with_context(|| format!("hash check of {} failed", url))?;
-        } else {
-            echo!("Checksum check skipped (no hash available)");
+    if args.list_shells {
+        for shell in ShellType::value_variants() {
+            if let Some(value) = shell.to_possible_value() {
+                echo!("{}", value.get_name());
+            }
         }
+        return Ok(());
+    }
 
-        let tmp = tempfile::NamedTempFile::new()?;
-
-        // unix currently comes compressed, windows comes uncompressed
-        #[cfg(unix)]
-        {
-            use std::io::Read;
-            let mut decoder = flate2::bufread::GzDecoder::new(&bytes[..]);
-            let mut rv = Vec::new();
-            decoder.read_to_end(&mut rv)?;
-            fs::write(tmp.path(), rv)?;
+    if args.all {
+        let output_dir = args
+            .output_dir
+            .as_ref()
+            .context("--all requires --output-dir")?;
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("could not create folder {}", output_dir.display()))?;
+        for shell in ShellType::value_variants() {
+            let path = output_dir.join(shell.file_name());
+            let mut out = fs::File::create(&path)
+                .with_context(|| format!("could not create {}", path.display()))?;
+            write_completion(*shell, args.show_hidden, &args.bin_name, &mut out);
+            echo!(
+                "Wrote completion script to {}",
+                style(path.display()).cyan()
+            );
         }
-        #[cfg(windows)]
-        {
-            fs::write(tmp.path(), bytes)?;
+        return Ok(());
+    }
+
+    let mut out: Box<dyn Write> = match args.output {
+        Some(ref path) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("could not create folder {}", parent.display()))?;
+            }
+            Box::new(
+                fs::File::create(path)
+                    .with_context(|| format!("could not create {}", path.display()))?,
+            )
         }
-        update_exe_and_shims(tmp.path())?;
+        None => Box::new(std::io::stdout()),
+    };
+    let shell = args.shell.unwrap_or(ShellType::Bash);
+    if args.lazy {
+        write_lazy_completion(shell, args.show_hidden, &args.bin_name, &mut out)?;
+    } else {
+        write_completion(shell, args.show_hidden, &args.bin_name, &mut out);
     }
 
-    echo!("Updated!");
-    echo!();
-    Command::new(current_exe).arg("--version").status()?;
+    if let Some(ref path) = args.output {
+        echo!(
+            "Wrote completion script to {}",
+            style(path.display()).cyan()
+        );
+    }
 
     Ok(())
 }
 
-fn update_exe_and_shims(new_exe: &Path) -> Result<(), Error> {
-    let app_dir = get_app_dir().canonicalize()?;
-    let current_exe = env::current_exe()?.canonicalize()?;
-    let shims = app_dir.join("shims");
-
-    self_replace::self_replace(new_exe)?;
-
-    // if the shims have been created before (they really should have)
-    // we want to make sure that they point to the new executable now.
-    // for symlinks that probably is not necessary, but for hardlinks
-    // that's very important.
-    if shims.is_dir() {
-        update_core_shims(&shims, &current_exe)?;
+/// Recursively unhides all subcommands of a command.
+fn unhide_subcommands(mut cmd: clap::Command) -> clap::Command {
+    let names = cmd
+        .get_subcommands()
+        .map(|x| x.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in names {
+        cmd = cmd.mut_subcommand(name, |sub| unhide_subcommands(sub.hide(false)));
     }
-
-    Ok(())
+    cmd
 }
 
-fn install(args: InstallCommand) -> Result<(), Error> {
-    perform_install(
-        if args.yes {
-            InstallMode::NoPrompts
+/// Writes a stub that loads the real completion script on first use.
+fn write_lazy_completion(
+    shell: ShellType,
+    show_hidden: bool,
+    bin_name: &str,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let command = format!(
+        "{bin_name} self completion --shell {}{}{}",
+        shell.to_possible_value().unwrap().get_name(),
+        if show_hidden { " --show-hidden" } else { "" },
+        if bin_name != "rye" {
+            format!(" --bin-name {}", bin_name)
         } else {
-            InstallMode::Default
-        },
-        args.toolchain.as_deref(),
-    )
+            String::new()
+        }
+    );
+    let stub = match shell {
+        ShellType::Bash => format!(
+            "_{bin_name}_lazy() {{\n    \
+             unset -f _{bin_name}_lazy\n    \
+             eval \"$({command})\"\n    \
+             _{bin_name} \"$@\"\n\
+             }}\n\
+             complete -F _{bin_name}_lazy -o bashdefault -o default {bin_name}\n"
+        ),
+        ShellType::Zsh => format!(
+            "_{bin_name}_lazy() {{\n    \
+             unfunction _{bin_name}_lazy\n    \
+             eval \"$({command})\"\n    \
+             _{bin_name} \"$@\"\n\
+             }}\n\
+             compdef _{bin_name}_lazy {bin_name}\n"
+        ),
+        // fish already loads completions on first use from its completions
+        // folder, so the stub only needs to defer generating them.
+        ShellType::Fish => format!("{command} | source\n"),
+        ShellType::Elvish | ShellType::Nushell | ShellType::Powershell => {
+            bail!("lazy completions are not supported for this shell")
+        }
+    };
+    out.write_all(stub.as_bytes())?;
+    Ok(())
 }
 
-fn remove_dir_all_if_exists(path: &Path) -> Result<(), Error> {
-    if path.is_dir() {
-        fs::remove_dir_all(path)?;
+fn write_completion(shell: ShellType, show_hidden: bool, bin_name: &str, out: &mut dyn Write) {
+    let mut cmd = super::Args::command();
+    if show_hidden {
+        cmd = unhide_subcommands(cmd);
     }
-    Ok(())
+    let shell = match shell {
+        ShellType::Bash => Shell::Bash,
+        ShellType::Elvish => Shell::Elvish,
+        ShellType::Fish => Shell::Fish,
+        ShellType::Nushell => {
+            clap_complete::generate(Nushell, &mut cmd, bin_name, out);
+            return;
+        }
+        ShellType::Powershell => Shell::PowerShell,
+        ShellType::Zsh => Shell::Zsh,
+    };
+    clap_complete::generate(shell, &mut cmd, bin_name, out);
 }
 
-fn uninstall(args: UninstallCommand) -> Result<(), Error> {
-    if !args.yes
-        && !dialoguer::Confirm::new()
-            .with_prompt("Do you want to uninstall rye?")
-            .interact()?
-    {
+fn print_env(args: EnvCommand) -> Result<(), Error> {
+    let app_dir = get_app_dir();
+    if args.path {
+        echo!("{}", app_dir.join("env").display());
         return Ok(());
     }
+    echo!(
+        "{}",
+        render_shell_env(args.shell.unwrap_or(ShellType::Bash), app_dir)?.trim()
+    );
+    Ok(())
+}
 
-    let app_dir = get_app_dir();
-    if app_dir.is_dir() {
-        let real_exe = env::current_exe()?.canonicalize()?;
-        let real_app_dir = app_dir.canonicalize()?;
-
-        // try to delete all shims that can be found.  Ignore if deletes don't work.
-        // The delete of the current executable for instance will fail on windows.
-        let shim_dir = app_dir.join("shims");
-        if let Ok(dir) = shim_dir.read_dir() {
-            for entry in dir.flatten() {
-                fs::remove_file(&entry.path()).ok();
-            }
-        }
-
-        remove_dir_all_if_exists(&app_dir.join("self"))?;
-        remove_dir_all_if_exists(&app_dir.join("py"))?;
-        remove_dir_all_if_exists(&app_dir.join("pip-tools"))?;
-
-        // special deleting logic if we are placed in the app dir and the shim deletion
-        // did not succeed.  This is likely the case on windows where we then use the
-        // `self_delete` crate.
-        if real_exe.strip_prefix(&real_app_dir).is_ok() && real_exe.is_file() {
-            self_delete_outside_path(&real_app_dir)?;
+/// Renders the statements that export `RYE_HOME` and put the shims on the `PATH`.
+fn render_shell_env(shell: ShellType, app_dir: &Path) -> Result<String, Error> {
+    let rye_home = app_dir.display().to_string();
+    let shims = app_dir.join("shims").display().to_string();
+    Ok(match shell {
+        ShellType::Bash | ShellType::Zsh => render_env_file(None, true, &rye_home)?,
+        ShellType::Fish => format!(
+            "set -gx RYE_HOME \"{rye_home}\"\n\
+             contains -- \"{shims}\" $PATH; or set -gx PATH \"{shims}\" $PATH\n"
+        ),
+        ShellType::Powershell => format!(
+            "$env:RYE_HOME = \"{rye_home}\"\n\
+             $env:PATH = \"{shims}{}\" + $env:PATH\n",
+            if cfg!(windows) { ';' } else { ':' }
+        ),
+        ShellType::Elvish | ShellType::Nushell => {
+            bail!("printing the env is not supported for this shell")
         }
+    })
+}
 
-        // at this point the remaining shim folder should be deletable
-        remove_dir_all_if_exists(&app_dir.join("shims"))?;
+fn fix_shims(_args: FixShimsCommand) -> Result<(), Error> {
+    let shims = get_app_dir().join("shims");
+    let rye_exe = get_installed_rye_exe(get_app_dir());
 
-        // leave this empty behind in case someone sourced it.  The config also stays around.
-        let env_file = app_dir.join("env");
-        if env_file.is_file() {
-            fs::write(env_file, "")?;
-        }
+    fs::create_dir_all(&shims).context("tried to create shim folder")?;
+    if !rye_exe.is_file() {
+        fs::copy(env::current_exe()?, &rye_exe).context("tried to restore rye executable")?;
+        echo!(
+            "Restored missing binary {}",
+            style(rye_exe.display()).cyan()
+        );
     }
+    update_core_shims(&shims, &rye_exe)?;
 
-    echo!("Done!");
-    echo!();
+    // linux always uses hardlinks, see update_core_shims
+    let link_type = if shims.join(PORTABLE_MARKER).is_file() {
+        "relative wrappers"
+    } else if symlinks_supported() && !cfg!(target_os = "linux") {
+        "symlinks"
+    } else {
+        "hardlinks"
+    };
+    echo!(
+        "Repaired shims in {} (using {})",
+        style(shims.display()).cyan(),
+        link_type
+    );
+    Ok(())
+}
 
-    let rye_home = env::var("RYE_HOME")
-        .map(Cow::Owned)
-        .unwrap_or(Cow::Borrowed(DEFAULT_HOME));
-    if cfg!(unix) {
+fn which(_args: WhichCommand) -> Result<(), Error> {
+    let current_exe = env::current_exe()?.canonicalize()?;
+    let installed_exe = get_installed_rye_exe(get_app_dir());
+    let real_installed_exe = installed_exe.canonicalize().ok();
+
+    echo!("running:   {}", style(current_exe.display()).cyan());
+    if real_installed_exe.is_some() {
+        echo!("installed: {}", style(installed_exe.display()).cyan());
+    } else {
         echo!(
-            "Don't forget to remove the sourcing of {} from your shell config.",
-            Path::new(&rye_home as &str).join("env").display()
+            "installed: {} (missing)",
+            style(installed_exe.display()).cyan()
         );
+    }
+    if let Some(version) = read_shims_version(&get_app_dir().join("shims")) {
+        echo!("shims:     rye {}", style(version).cyan());
+    }
+    if real_installed_exe.as_ref() == Some(&current_exe) {
+        echo!("The running rye is the installed one.");
     } else {
-        echo!(
-            "Don't forget to remove {} from your PATH",
-            Path::new(&rye_home as &str).join("shims").display()
-        )
+        echo!("The running rye is not the installed one.");
     }
 
-    Ok(())
-}
+    // another rye earlier on the path wins over the shim
+    if let Ok(found) = which::which(installed_exe.file_name().unwrap_or_default()) {
+        if found.canonicalize().ok() != real_installed_exe {
+            warn!(
+                "{} on PATH shadows the installed rye",
+                style(found.display()).cyan()
+            );
+        }
+    }
 
-#[cfg(unix)]
-fn is_fish() -> bool {
-    use whattheshell::Shell;
-    Shell::infer().map_or(false, |x| matches!(x, Shell::Fish))
+    Ok(())
 }
 
-fn perform_install(mode: InstallMode, toolchain_path: Option<&Path>) -> Result<(), Error> {
-    let exe = env::current_exe()?;
+fn doctor(_args: DoctorCommand) -> Result<(), Error> {
     let app_dir = get_app_dir();
     let shims = app_dir.join("shims");
-    let target = shims.join("rye").with_extension(EXE_EXTENSION);
-
-    echo!("{}", style("Welcome to Rye!").bold());
-
-    if matches!(mode, InstallMode::AutoInstall) {
-        echo!();
-        echo!("Rye has detected that it's not installed on this computer yet and");
-        echo!("automatically started the installer for you.  For more information");
-        echo!(
-            "read {}",
-            style("https://rye-up.com/guide/installation/").yellow()
-        );
-    }
+    let rye_exe = get_installed_rye_exe(app_dir);
+    let mut failed = false;
+    let mut report = |ok: bool, critical: bool, msg: String| {
+        let status = if ok {
+            style("ok").green()
+        } else if critical {
+            failed = true;
+            style("fail").red()
+        } else {
+            style("warn").yellow()
+        };
+        echo!("[{}] {}", status, msg);
+    };
 
-    echo!();
-    echo!(
-        "This installer will install rye to {}",
-        style(app_dir.display()).cyan()
+    let on_path = is_on_path(&shims);
+    report(
+        on_path,
+        true,
+        format!("shims folder {} is on PATH", style(shims.display()).cyan()),
     );
-    echo!(
-        "This path can be changed by exporting the {} environment variable.",
-        style("RYE_HOME").cyan()
-    );
-    echo!();
-    echo!("{}", style("Details:").bold());
-    echo!("  Rye Version: {}", style(env!("CARGO_PKG_VERSION")).cyan());
-    echo!("  Platform: {} ({})", style(OS).cyan(), style(ARCH).cyan());
 
-    if cfg!(windows) && !symlinks_supported() {
-        echo!();
-        warn!("your Windows configuration does not support symlinks.");
-        echo!();
-        echo!("It's strongly recommended that you enable developer mode in Windows to");
-        echo!("enable symlinks.  You need to enable this before continuing the setup.");
-        echo!(
-            "Learn more at {}",
-            style("https://rye-up.com/guide/faq/#windows-developer-mode").yellow()
-        );
+    match which::which(rye_exe.file_name().unwrap_or_default()) {
+        Ok(found) => {
+            let resolves = found.canonicalize().ok() == rye_exe.canonicalize().ok();
+            report(
+                resolves,
+                true,
+                format!(
+                    "rye resolves to {} (expected {})",
+                    style(found.display()).cyan(),
+                    style(rye_exe.display()).cyan()
+                ),
+            );
+        }
+        Err(_) => report(false, true, "rye can be found on PATH".into()),
     }
 
-    echo!();
-    if !matches!(mode, InstallMode::NoPrompts)
-        && !dialoguer::Confirm::new()
-            .with_prompt("Continue?")
-            .interact()?
-    {
-        elog!("Installation cancelled!");
-        return Err(QuietExit(1).into());
+    let python = get_venv_python_bin(&app_dir.join("self"));
+    let python_version = Command::new(&python)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_string());
+    match python_version {
+        Some(version) => report(
+            true,
+            true,
+            format!("self-venv python launches ({})", version),
+        ),
+        None => report(
+            false,
+            true,
+            format!(
+                "self-venv python launches ({})",
+                style(python.display()).cyan()
+            ),
+        ),
     }
 
-    // place executable in rye home folder
-    fs::create_dir_all(&shims).ok();
-    if target.is_file() {
-        fs::remove_file(&target)?;
-    }
-    fs::copy(exe, &target)?;
-    echo!("Installed binary to {}", style(target.display()).cyan());
+    let installed_version = get_rye_version(&rye_exe);
+    let shims_version = read_shims_version(&shims);
+    report(
+        shims_version.is_some() && shims_version == installed_version,
+        false,
+        format!(
+            "shims point to rye {}",
+            style(shims_version.as_deref().unwrap_or("(unknown)")).cyan()
+        ),
+    );
 
-    // write an env file we can source later.  Prefer $HOME/.rye over
-    // the expanded path, if not overridden.
-    let (custom_home, rye_home) = env::var("RYE_HOME")
-        .map(|x| (true, Cow::Owned(x)))
-        .unwrap_or((false, Cow::Borrowed(DEFAULT_HOME)));
+    report(symlinks_supported(), false, "symlinks are supported".into());
 
-    if cfg!(unix) {
-        fs::write(
-            app_dir.join("env"),
-            render!(UNIX_ENV_FILE, custom_home, rye_home),
-        )?;
+    if failed {
+        Err(QuietExit(1).into())
+    } else {
+        Ok(())
     }
+}
 
-    // Register a toolchain if provided.
-    if let Some(toolchain_path) = toolchain_path {
+fn version(args: VersionCommand) -> Result<(), Error> {
+    if !args.verbose {
+        echo!("rye {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    super::print_version()?;
+    echo!("app dir: {}", get_app_dir().display());
+    echo!(
+        "self-venv python: {}",
+        get_self_venv_python_version()
+            .as_deref()
+            .unwrap_or("not bootstrapped")
+    );
+    Ok(())
+}
+
+fn update(args: UpdateCommand) -> Result<(), Error> {
+    if !matches!(args.format, Some(Format::Json)) {
+        let output = CommandOutput::from_quiet_and_verbose(false, args.verbose);
+        return match run_update(args, output) {
+            Ok(_) => Ok(()),
+            Err(err) if is_no_update_available(&err) => {
+                echo!("already up to date");
+                Err(QuietExit(UP_TO_DATE_EXIT_CODE).into())
+            }
+            Err(err) => Err(err),
+        };
+    }
+
+    // every error, including those before the update starts, is reported
+    // in the json output.
+    let rv = match run_update(args, CommandOutput::Quiet) {
+        Ok(record) => serde_json::to_value(record)?,
+        Err(err) => match err.downcast_ref::<SelfUpdateError>() {
+            Some(SelfUpdateError::NoUpdateAvailable { version }) => {
+                serde_json::to_value(UpdateRecord::up_to_date(version.clone()))?
+            }
+            _ => json!({ "error": format!("{:#}", err) }),
+        },
+    };
+    serde_json::to_writer_pretty(std::io::stdout().lock(), &rv)?;
+    echo!();
+    if rv.get("error").is_some() {
+        Err(QuietExit(1).into())
+    } else if rv.get("up_to_date") == Some(&json!(true)) {
+        Err(QuietExit(UP_TO_DATE_EXIT_CODE).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns `true` if an update failed because the version is already installed.
+fn is_no_update_available(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref::<SelfUpdateError>(),
+        Some(SelfUpdateError::NoUpdateAvailable { .. })
+    )
+}
+
+/// Runs `rye self update` and returns the record of the update if one ran.
+fn run_update(args: UpdateCommand, output: CommandOutput) -> Result<Option<UpdateRecord>, Error> {
+    // the proxy is picked up from the environment by all downloads and
+    // also passed on to the commands we spawn.
+    if let Some(ref proxy) = args.proxy {
+        env::set_var("HTTPS_PROXY", proxy);
+    }
+    if let Some(ref ca_bundle) = args.ca_bundle {
+        env::set_var("RYE_CA_BUNDLE", env::current_dir()?.join(ca_bundle));
+    }
+    // --release-url overrides RYE_RELEASE_URL for the rest of the process,
+    // downloads use it to decide which URLs get the mirror credentials.
+    if let Some(ref release_url) = args.release_url {
+        env::set_var("RYE_RELEASE_URL", release_url);
+    }
+    if let Some(retries) = args.retries {
+        set_download_retries(retries);
+    }
+    if let Some(timeout) = args.timeout {
+        set_download_timeout(timeout);
+    }
+    if args.json_progress {
+        set_json_progress(true);
+    }
+
+    // only the read-only operations can run next to other rye operations
+    let _lock = if args.check || args.list.is_some() {
+        None
+    } else {
+        Some(lock_app_dir(get_app_dir(), APP_DIR_LOCK_WAIT)?)
+    };
+
+    if args.rollback {
+        return rollback().map(|_| None);
+    }
+
+    if args.shims_only {
+        return update_shims_only().map(|_| None);
+    }
+
+    let channel = args.channel.unwrap_or_else(|| {
+        Config::current()
+            .update_channel()
+            .and_then(|x| Channel::from_str(&x, true).ok())
+            .unwrap_or(Channel::Stable)
+    });
+
+    if let Some(limit) = args.list {
+        return list_releases(&get_release_url(args.release_url.as_deref()), limit).map(|_| None);
+    }
+
+    if args.check {
+        return check_for_update(&get_release_url(args.release_url.as_deref()), channel)
+            .map(|_| None);
+    }
+
+    // the channel sticks for later updates, --check and --list only peek
+    if let Some(channel) = args.channel {
+        save_update_channel(channel)?;
+    }
+
+    Ok(Some(self_update(args, channel, output)?))
+}
+
+/// Output structure for self update --format=json
+#[derive(Serialize, Debug)]
+struct UpdateRecord {
+    previous_version: &'static str,
+    new_version: Option<String>,
+    download_url: Option<String>,
+    checksum: CheckStatus,
+    signature: CheckStatus,
+    shims_updated: bool,
+    completes_on_exit: bool,
+    up_to_date: bool,
+}
+
+impl UpdateRecord {
+    fn new() -> UpdateRecord {
+        UpdateRecord {
+            previous_version: env!("CARGO_PKG_VERSION"),
+            new_version: None,
+            download_url: None,
+            checksum: CheckStatus::Skipped,
+            signature: CheckStatus::Skipped,
+            shims_updated: false,
+            completes_on_exit: false,
+            up_to_date: false,
+        }
+    }
+
+    /// The record of an update that found the version already installed.
+    fn up_to_date(version: String) -> UpdateRecord {
+        UpdateRecord {
+            new_version: Some(version),
+            up_to_date: true,
+            ..UpdateRecord::new()
+        }
+    }
+
+    fn set_replacement(&mut self, replacement: ExeReplacement) {
+        match replacement {
+            ExeReplacement::Done { shims_updated } => self.shims_updated = shims_updated,
+            ExeReplacement::Scheduled => self.completes_on_exit = true,
+        }
+    }
+}
+
+/// The outcome of replacing the executable.
+enum ExeReplacement {
+    /// The executable was replaced right away.
+    Done { shims_updated: bool },
+    /// The executable is in use and is replaced once rye exits.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Scheduled,
+}
+
+#[derive(Serialize, Debug, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+    Verified,
+    Skipped,
+}
+
+/// The ways a self update can fail.
+///
+/// The variants wrapping an [`Error`] are transparent, they display like the
+/// error they wrap.
+#[derive(Debug)]
+pub enum SelfUpdateError {
+    /// The release or its checksum could not be downloaded.
+    DownloadFailed(Error),
+    /// The downloaded release does not match its checksum.
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+        /// Where the mismatching download was saved for inspection.
+        saved_to: Option<PathBuf>,
+    },
+    /// The rye executable could not be replaced.
+    ReplaceFailed(Error),
+    /// The requested version is already installed.
+    NoUpdateAvailable { version: String },
+    /// Any other failure.
+    Other(Error),
+}
+
+impl std::error::Error for SelfUpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SelfUpdateError::DownloadFailed(err)
+            | SelfUpdateError::ReplaceFailed(err)
+            | SelfUpdateError::Other(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SelfUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelfUpdateError::DownloadFailed(err)
+            | SelfUpdateError::ReplaceFailed(err)
+            | SelfUpdateError::Other(err) => write!(f, "{}", err),
+            SelfUpdateError::ChecksumMismatch {
+                name,
+                expected,
+                actual,
+                saved_to,
+            } => {
+                write!(
+                    f,
+                    "hash check of {} failed: expected {} got {}",
+                    name, expected, actual
+                )?;
+                if let Some(saved_to) = saved_to {
+                    write!(f, ", the download was saved to {}", saved_to.display())?;
+                }
+                Ok(())
+            }
+            SelfUpdateError::NoUpdateAvailable { version } => {
+                write!(f, "rye {} is already installed", version)
+            }
+        }
+    }
+}
+
+/// Updates rye and reports failures as [`SelfUpdateError`].
+fn self_update(
+    args: UpdateCommand,
+    channel: Channel,
+    output: CommandOutput,
+) -> Result<UpdateRecord, SelfUpdateError> {
+    // temporary files are removed and the executable is either replaced or
+    // left alone if the update is interrupted.
+    let _interrupt_guard = catch_interrupts();
+    match perform_update(args, channel, output) {
+        Ok(record) if record.up_to_date => Err(SelfUpdateError::NoUpdateAvailable {
+            version: record.new_version.unwrap_or_default(),
+        }),
+        Ok(record) => Ok(record),
+        Err(err) => Err(err
+            .downcast::<SelfUpdateError>()
+            .unwrap_or_else(SelfUpdateError::Other)),
+    }
+}
+
+fn perform_update(
+    args: UpdateCommand,
+    channel: Channel,
+    output: CommandOutput,
+) -> Result<UpdateRecord, Error> {
+    // make sure to read the exe before self_replace as otherwise we might read
+    // a bad executable name on Linux where the move is picked up.
+    let current_exe = env::current_exe()?;
+    let mut record = UpdateRecord::new();
+
+    // git based installation with cargo.  The builds are kept per rev or tag
+    // so that updating to the same one again does not need to rebuild.
+    if args.rev.is_some() || args.tag.is_some() {
+        let (kind, reference) = match (&args.rev, &args.tag) {
+            (Some(rev), _) => ("--rev", rev.as_str()),
+            (None, Some(tag)) => ("--tag", tag.as_str()),
+            (None, None) => unreachable!(),
+        };
+        let root = get_git_build_root(kind, reference);
+        let built_exe = root.join("bin").join("rye").with_extension(EXE_EXTENSION);
+        if !args.no_cache && !args.force && built_exe.is_file() {
+            if output != CommandOutput::Quiet {
+                echo!("Using cached build of {}", style(reference).cyan());
+            }
+        } else {
+            let mut cmd = Command::new("cargo");
+            cmd.arg("install")
+                .arg("--git")
+                .arg(GITHUB_REPO)
+                .arg("--root")
+                .env(
+                    "PATH",
+                    join_paths(
+                        Some(root.join("bin"))
+                            .into_iter()
+                            .chain(split_paths(&env::var_os("PATH").unwrap_or_default())),
+                    )?,
+                )
+                .arg(&root)
+                .arg(kind)
+                .arg(reference);
+            // cargo would otherwise consider the cached build up to date
+            if args.force || args.no_cache {
+                cmd.arg("--force");
+            }
+            cmd.arg("rye");
+            if output == CommandOutput::Quiet {
+                cmd.stdout(Stdio::null());
+                cmd.stderr(Stdio::null());
+            }
+            let status = cmd.status().context("unable to update via cargo-install")?;
+            if !status.success() {
+                bail!("failed to self-update via cargo-install");
+            }
+        }
+        record.set_replacement(
+            update_exe_and_shims(&built_exe).map_err(SelfUpdateError::ReplaceFailed)?,
+        );
+    } else if let Some(ref path) = args.from_file {
+        let tmp = update_tempfile()?;
+        let compressed = path.extension().map_or(false, |x| x == "gz" || x == "zst");
+        // a signature is picked up from next to the file like for downloads
+        let mut signature_path = path.clone().into_os_string();
+        signature_path.push(".minisig");
+        let signature_path = PathBuf::from(signature_path);
+        let signature = if signature_path.is_file() {
+            Some(
+                fs::read(&signature_path)
+                    .with_context(|| format!("could not read {}", signature_path.display()))?,
+            )
+        } else if args.require_signature {
+            bail!(
+                "signature check of {} failed (no signature available)",
+                path.display()
+            );
+        } else {
+            None
+        };
+        if args.checksum.is_some() || signature.is_some() {
+            let bytes =
+                fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+            if let Some(ref checksum) = args.checksum {
+                if output != CommandOutput::Quiet {
+                    echo!("Checking checksum");
+                }
+                check_checksum(&bytes, checksum)
+                    .with_context(|| format!("hash check of {} failed", path.display()))?;
+                record.checksum = CheckStatus::Verified;
+            } else if output != CommandOutput::Quiet {
+                echo!("Checksum check skipped (no --checksum given)");
+            }
+            if let Some(ref signature) = signature {
+                if output != CommandOutput::Quiet {
+                    echo!("Checking signature");
+                }
+                check_signature(&bytes, signature, RELEASE_PUBLIC_KEY)
+                    .with_context(|| format!("signature check of {} failed", path.display()))?;
+                record.signature = CheckStatus::Verified;
+            }
+            if compressed {
+                write_decompressed(&bytes[..], bytes.len() as u64, tmp.path(), output)?;
+            } else {
+                fs::write(tmp.path(), bytes)?;
+            }
+        } else {
+            if output != CommandOutput::Quiet {
+                echo!("Checksum check skipped (no --checksum given)");
+                echo!("Signature check skipped (no signature available)");
+            }
+            if compressed {
+                let file = fs::File::open(path)
+                    .with_context(|| format!("could not read {}", path.display()))?;
+                let len = file.metadata()?.len();
+                write_decompressed(std::io::BufReader::new(file), len, tmp.path(), output)?;
+            } else {
+                fs::copy(path, tmp.path())
+                    .with_context(|| format!("could not read {}", path.display()))?;
+            }
+        }
+        record.set_replacement(
+            update_exe_and_shims(tmp.path()).map_err(SelfUpdateError::ReplaceFailed)?,
+        );
+    } else {
+        let release_url = get_release_url(args.release_url.as_deref());
+        let version = match args.version {
+            Some(ref version) => Cow::Borrowed(version.as_str()),
+            None if channel == Channel::Prerelease => {
+                Cow::Owned(get_latest_release_tag(&release_url, channel)?)
+            }
+            None => Cow::Borrowed("latest"),
+        };
+
+        // skip replacing the executable with an identical one unless forced
+        let target = if version != "latest" {
+            Some(version.to_string())
+        } else if !args.force {
+            get_latest_release_tag(&release_url, channel).ok()
+        } else {
+            None
+        };
+        if !args.force && target.as_deref().map_or(false, is_current_version) {
+            record.up_to_date = true;
+            record.new_version = Some(env!("CARGO_PKG_VERSION").to_string());
+            if args.repair {
+                repair_internals(true, output)?;
+            }
+            return Ok(record);
+        }
+        if !args.allow_downgrade {
+            if let Some(ref target) = target {
+                check_not_downgrade(target)?;
+            }
+        }
+
+        if output != CommandOutput::Quiet {
+            echo!("Updating to {version}");
+        }
+        let binary = format!("rye-{ARCH}-{OS}");
+        let ext = if cfg!(unix) { ".gz" } else { ".exe" };
+        let url = get_release_asset_url(&release_url, &version, &format!("{binary}{ext}"));
+        // credentials of the mirror must not show up in the output
+        let display_url = redact_url(&url).into_owned();
+        if output == CommandOutput::Verbose {
+            echo!("Download URL: {}", style(&display_url).cyan());
+            echo!(
+                "Checksum URL: {}",
+                style(format!("{}.sha256", display_url)).cyan()
+            );
+            if version == "latest" {
+                match resolve_redirect_url(&url) {
+                    Ok(target) => echo!("Resolved to: {}", style(redact_url(&target)).cyan()),
+                    Err(err) => warn!("could not resolve {}: {:#}", display_url, err),
+                }
+            }
+        }
+        let cached = if args.no_cache {
+            None
+        } else {
+            read_cached_release(&url)
+        };
+        let bytes = if let Some((bytes, checksum)) = cached {
+            if output != CommandOutput::Quiet {
+                echo!("Using cached download");
+            }
+            check_release_checksum(&bytes, &checksum, &format!("cached {}", display_url))?;
+            record.checksum = CheckStatus::Verified;
+            bytes
+        } else {
+            check_free_space(&url, &current_exe)?;
+            let bytes = download_url(&url, output)
+                .with_context(|| format!("could not download release {version} for this platform"))
+                .map_err(SelfUpdateError::DownloadFailed)?;
+            if let Some(checksum) =
+                download_checksum(&url, output).map_err(SelfUpdateError::DownloadFailed)?
+            {
+                if output != CommandOutput::Quiet {
+                    echo!("Checking checksum");
+                }
+                check_release_checksum(&bytes, &checksum, &display_url)?;
+                record.checksum = CheckStatus::Verified;
+                // failing to cache is not fatal, it only makes retries slower
+                write_cached_release(&url, &bytes, &checksum).ok();
+            } else if output != CommandOutput::Quiet {
+                echo!("Checksum check skipped (no hash available)");
+            }
+            bytes
+        };
+
+        let signature = download_url_ignore_404(&format!("{}.minisig", url), output)?;
+        if let Some(signature) = signature {
+            if output != CommandOutput::Quiet {
+                echo!("Checking signature");
+            }
+            check_signature(&bytes, &signature, RELEASE_PUBLIC_KEY)
+                .with_context(|| format!("signature check of {} failed", display_url))?;
+            record.signature = CheckStatus::Verified;
+        } else if args.require_signature {
+            bail!(
+                "signature check of {} failed (no signature available)",
+                display_url
+            );
+        } else if output != CommandOutput::Quiet {
+            echo!("Signature check skipped (no signature available)");
+        }
+
+        let tmp = update_tempfile()?;
+
+        // unix currently comes compressed, windows comes uncompressed
+        #[cfg(unix)]
+        {
+            write_decompressed(&bytes[..], bytes.len() as u64, tmp.path(), output)?;
+        }
+        #[cfg(windows)]
+        {
+            fs::write(tmp.path(), bytes)?;
+        }
+        record.set_replacement(
+            update_exe_and_shims(tmp.path()).map_err(SelfUpdateError::ReplaceFailed)?,
+        );
+        remove_cached_release(&url);
+        record.download_url = Some(display_url);
+    }
+
+    if record.completes_on_exit {
+        if output != CommandOutput::Quiet {
+            echo!("The rye executable is in use.  The update completes once rye exits");
+            echo!("and takes effect on the next launch.");
+        }
+    } else {
+        repair_internals(args.repair, output)?;
+        if output != CommandOutput::Quiet {
+            echo!("Updated!");
+            echo!();
+            Command::new(&current_exe).arg("--version").status()?;
+        } else {
+            record.new_version = get_rye_version(&current_exe);
+        }
+        if let Some(hook) = env::var_os("RYE_POST_UPDATE_HOOK") {
+            let new_version = record
+                .new_version
+                .clone()
+                .or_else(|| get_rye_version(&current_exe))
+                .unwrap_or_default();
+            run_post_update_hook(
+                Path::new(&hook),
+                record.previous_version,
+                &new_version,
+                output,
+            );
+        }
+    }
+
+    Ok(record)
+}
+
+/// Recreates rye's internals if they are incomplete or `force` is set.
+///
+/// The internals are incomplete if the interpreter of the self venv is
+/// missing, for instance because `self` or the toolchain in `py` is gone.
+/// They are bootstrapped by the running executable, the new one upgrades
+/// them on first use if its requirements differ.  Unless forced, failing to
+/// repair them (for instance without network access) only warns as they are
+/// otherwise bootstrapped on first use.
+fn repair_internals(force: bool, output: CommandOutput) -> Result<(), Error> {
+    let self_venv = get_app_dir().join("self");
+    let incomplete = !get_venv_python_bin(&self_venv).is_file();
+    if !force && !incomplete {
+        return Ok(());
+    }
+    if output != CommandOutput::Quiet {
+        if incomplete {
+            echo!("Rye's internals are incomplete, repairing them");
+        } else {
+            echo!("Repairing rye's internals");
+        }
+    }
+    remove_dir_all_if_exists(&self_venv)?;
+    match ensure_self_venv(output) {
+        Ok(_) => Ok(()),
+        Err(err) if !force => {
+            warn!("could not repair rye's internals: {:#}", err);
+            Ok(())
+        }
+        Err(err) => Err(err.context("could not repair rye's internals")),
+    }
+}
+
+/// Runs the `RYE_POST_UPDATE_HOOK` script after a successful update.
+///
+/// The old and new version are passed as arguments and in the `RYE_OLD_VERSION`
+/// and `RYE_NEW_VERSION` environment variables.  Failures only warn.
+fn run_post_update_hook(hook: &Path, old_version: &str, new_version: &str, output: CommandOutput) {
+    let mut cmd = Command::new(hook);
+    cmd.arg(old_version)
+        .arg(new_version)
+        .env("RYE_OLD_VERSION", old_version)
+        .env("RYE_NEW_VERSION", new_version);
+    // keep machine readable output intact
+    if output == CommandOutput::Quiet {
+        cmd.stdout(Stdio::null());
+    }
+    match cmd.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("post update hook {} failed ({})", hook.display(), status),
+        Err(err) => warn!("could not run post update hook {}: {}", hook.display(), err),
+    }
+}
+
+/// Reports the bytes consumed from a reader as JSON progress.
+struct JsonProgressReader<R> {
+    inner: R,
+    progress: JsonProgress,
+    done: u64,
+}
+
+impl<R: std::io::Read> std::io::Read for JsonProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.done += n as u64;
+        self.progress.set_position(self.done);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for JsonProgressReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.done += amt as u64;
+        self.progress.set_position(self.done);
+    }
+}
+
+/// Decompresses a compressed release artifact into a file.
+///
+/// Releases are gzip compressed, but mirrors can also serve zstd compressed
+/// artifacts which are detected by their magic bytes.  The data is streamed
+/// so that the decompressed executable is never held in memory in its
+/// entirety.  `len` is the compressed size which is used for the progress bar.
+fn write_decompressed<R: std::io::BufRead>(
+    reader: R,
+    len: u64,
+    path: &Path,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let pb = if output != CommandOutput::Quiet && !json_progress_enabled() {
+        let pb = ProgressBar::new(len);
+        pb.set_style(
+            ProgressStyle::with_template("{spinner} Decompressing {wide_bar} {percent:>3}%")
+                .unwrap(),
+        );
+        pb
+    } else {
+        ProgressBar::hidden()
+    };
+    let mut reader = JsonProgressReader {
+        inner: pb.wrap_read(reader),
+        progress: JsonProgress::new("decompress", len),
+        done: 0,
+    };
+    let is_zstd = reader.fill_buf()?.starts_with(&ZSTD_MAGIC);
+    let mut file = fs::File::create(path)?;
+    let rv = if is_zstd {
+        std::io::copy(
+            &mut zstd::stream::read::Decoder::with_buffer(reader)?,
+            &mut file,
+        )
+    } else {
+        std::io::copy(&mut flate2::bufread::GzDecoder::new(reader), &mut file)
+    };
+    pb.finish_and_clear();
+    rv.context("could not decompress release")?;
+    Ok(())
+}
+
+/// Checks if a release tag refers to the running version of rye.
+fn is_current_version(tag: &str) -> bool {
+    let tag = tag.strip_prefix('v').unwrap_or(tag);
+    match (
+        tag.parse::<Version>(),
+        env!("CARGO_PKG_VERSION").parse::<Version>(),
+    ) {
+        (Ok(tag), Ok(current)) => tag == current,
+        _ => false,
+    }
+}
+
+/// Fails if a release tag is of an older major or minor version than rye.
+fn check_not_downgrade(tag: &str) -> Result<(), Error> {
+    let current = env!("CARGO_PKG_VERSION");
+    let (target_version, current_version) = match (
+        tag.strip_prefix('v').unwrap_or(tag).parse::<Version>(),
+        current.parse::<Version>(),
+    ) {
+        (Ok(target), Ok(current)) => (target, current),
+        _ => return Ok(()),
+    };
+    let major_minor = |v: &Version| (v.release.first().copied(), v.release.get(1).copied());
+    if major_minor(&target_version) < major_minor(&current_version) {
+        bail!(
+            "refusing to downgrade from {} to {} (pass --allow-downgrade to do it anyway)",
+            current,
+            tag
+        );
+    }
+    Ok(())
+}
+
+fn update_shims_only() -> Result<(), Error> {
+    let current_exe = env::current_exe()?.canonicalize()?;
+    let shims = get_app_dir().join("shims");
+    fs::create_dir_all(&shims).context("tried to create shim folder")?;
+    // this also recreates missing shims
+    update_core_shims(&shims, &current_exe)?;
+    write_shims_version(&shims, env!("CARGO_PKG_VERSION"))?;
+    echo!(
+        "Updated shims in {} to point to {}",
+        style(shims.display()).cyan(),
+        style(current_exe.display()).cyan()
+    );
+    Ok(())
+}
+
+fn check_for_update(release_url: &str, channel: Channel) -> Result<(), Error> {
+    let tag = get_latest_release_tag(release_url, channel)?;
+    let latest = tag
+        .strip_prefix('v')
+        .unwrap_or(&tag)
+        .parse::<Version>()
+        .map_err(|msg| anyhow!("could not determine latest version from {}: {}", tag, msg))?;
+    let current = env!("CARGO_PKG_VERSION")
+        .parse::<Version>()
+        .map_err(|msg| anyhow!("invalid rye version: {}", msg))?;
+
+    if latest > current {
+        echo!("update available: {}", latest);
+        Err(QuietExit(2).into())
+    } else {
+        echo!("up-to-date");
+        Ok(())
+    }
+}
+
+/// Downloads the checksum sidecar file of a release artifact.
+///
+/// The sha256 sidecar is preferred, a sha512 sidecar is used otherwise.  The
+/// returned checksum is prefixed with the algorithm unless it's sha256.
+fn download_checksum(url: &str, output: CommandOutput) -> Result<Option<String>, Error> {
+    if let Some(bytes) = download_url_ignore_404(&format!("{}.sha256", url), output)? {
+        return Ok(Some(String::from_utf8_lossy(&bytes).trim().to_string()));
+    }
+    Ok(
+        download_url_ignore_404(&format!("{}.sha512", url), output)?.map(|bytes| {
+            let checksum = String::from_utf8_lossy(&bytes).trim().to_string();
+            if checksum.contains(':') {
+                checksum
+            } else {
+                format!("sha512:{}", checksum)
+            }
+        }),
+    )
+}
+
+/// Returns the folder `cargo install` builds a rev or tag into.
+fn get_git_build_root(kind: &str, reference: &str) -> PathBuf {
+    let key = hex::encode(Sha256::digest(
+        format!("{}\0{}\0{}", GITHUB_REPO, kind, reference).as_bytes(),
+    ));
+    get_app_dir().join("self-builds").join(&key[..16])
+}
+
+/// Fails if there is not enough disk space to download and install a release.
+///
+/// The rye folder needs room for the cached download, for the decompressed
+/// executable, which is estimated to be three times the download, and for
+/// the backup of the current one.
+fn check_free_space(url: &str, current_exe: &Path) -> Result<(), Error> {
+    let size = match get_content_length(url) {
+        Some(size) => size,
+        None => return Ok(()),
+    };
+    let exe_size = fs::metadata(current_exe).map_or(0, |x| x.len());
+    let needed = size * 4 + exe_size;
+    if let Some((mount_point, available)) = get_available_space(get_app_dir()) {
+        if needed > available {
+            bail!(
+                "not enough disk space on {} to update: {:.1} MB needed but only {:.1} MB available",
+                mount_point.display(),
+                needed as f64 / (1024.0 * 1024.0),
+                available as f64 / (1024.0 * 1024.0)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the paths of the cached release artifact and its checksum.
+///
+/// The cache lives in the app dir rather than the shared temp dir so that
+/// other users cannot plant a release and a matching checksum.
+fn get_release_cache_paths(url: &str) -> (PathBuf, PathBuf) {
+    let key = hex::encode(Sha256::digest(url.as_bytes()));
+    let cache_dir = get_app_dir().join("self-update-cache");
+    (
+        cache_dir.join(format!("{}.bin", key)),
+        cache_dir.join(format!("{}.sha256", key)),
+    )
+}
+
+/// Returns a previously downloaded and verified release and its checksum.
+///
+/// Only recent downloads are reused so that a moved `latest` release is
+/// picked up again.
+fn read_cached_release(url: &str) -> Option<(Vec<u8>, String)> {
+    let (bin_path, checksum_path) = get_release_cache_paths(url);
+    let age = bin_path.metadata().ok()?.modified().ok()?.elapsed().ok()?;
+    if age > RELEASE_CACHE_MAX_AGE {
+        return None;
+    }
+    Some((
+        fs::read(bin_path).ok()?,
+        fs::read_to_string(checksum_path).ok()?,
+    ))
+}
+
+fn write_cached_release(url: &str, bytes: &[u8], checksum: &str) -> Result<(), Error> {
+    let (bin_path, checksum_path) = get_release_cache_paths(url);
+    if let Some(parent) = bin_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(bin_path, bytes)?;
+    fs::write(checksum_path, checksum)?;
+    Ok(())
+}
+
+fn remove_cached_release(url: &str) {
+    let (bin_path, checksum_path) = get_release_cache_paths(url);
+    fs::remove_file(bin_path).ok();
+    fs::remove_file(checksum_path).ok();
+}
+
+/// Prints the most recent `limit` releases and marks the installed one.
+fn list_releases(release_url: &str, limit: usize) -> Result<(), Error> {
+    // mirrors only host the release artifacts but not the API
+    if release_url != GITHUB_REPO {
+        bail!("listing releases is not supported with a release mirror");
+    }
+    let bytes = download_url(&get_github_api_releases_url(), CommandOutput::Quiet)
+        .context("could not fetch the list of releases")?;
+    let releases: Vec<GithubRelease> =
+        serde_json::from_slice(&bytes).context("could not parse the list of releases")?;
+
+    for release in releases.iter().filter(|x| !x.draft).take(limit) {
+        let date = release
+            .published_at
+            .as_deref()
+            .and_then(|x| x.get(..10))
+            .unwrap_or("unknown");
+        echo!(
+            "{} {}{}{}",
+            style(&release.tag_name).cyan(),
+            style(format!("({})", date)).dim(),
+            if release.prerelease {
+                " [pre-release]"
+            } else {
+                ""
+            },
+            if is_current_version(&release.tag_name) {
+                style(" (current)").green().to_string()
+            } else {
+                String::new()
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Creates the temporary file the new executable is written to.
+///
+/// It's placed in the rye home so that replacing the executable is a rename
+/// on the same file system rather than a copy.  If the home is not writable
+/// the system temp folder is used instead.
+fn update_tempfile() -> Result<tempfile::NamedTempFile, Error> {
+    match tempfile::Builder::new()
+        .prefix(".rye-update-")
+        .tempfile_in(get_app_dir())
+    {
+        Ok(tmp) => Ok(tmp),
+        Err(_) => Ok(tempfile::NamedTempFile::new()?),
+    }
+}
+
+/// Checks the checksum of a downloaded release.
+///
+/// On a mismatch the artifact is kept in a temporary file so that it can be
+/// inspected and its path is added to the error.
+fn check_release_checksum(bytes: &[u8], checksum: &str, name: &str) -> Result<(), Error> {
+    let (algorithm, expected) = checksum.split_once(':').unwrap_or(("sha256", checksum));
+    let actual = compute_checksum(bytes, algorithm)?;
+    if actual.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+    let saved_to = tempfile::Builder::new()
+        .prefix("rye-bad-download-")
+        .tempfile()
+        .and_then(|mut tmp| {
+            tmp.write_all(bytes)?;
+            tmp.keep().map_err(|err| err.error)
+        })
+        .ok()
+        .map(|(_, path)| path);
+    Err(SelfUpdateError::ChecksumMismatch {
+        name: name.to_string(),
+        expected: expected.to_string(),
+        actual,
+        saved_to,
+    }
+    .into())
+}
+
+/// Returns the tag of the most recent release on a channel.
+fn get_latest_release_tag(release_url: &str, channel: Channel) -> Result<String, Error> {
+    match channel {
+        // the latest release redirects to the tag of the most recent stable release
+        Channel::Stable => {
+            let resolved_url = resolve_redirect_url(&format!("{release_url}/releases/latest"))?;
+            Ok(resolved_url.rsplit('/').next().unwrap_or("").to_string())
+        }
+        // pre-releases can only be discovered via the GitHub API
+        Channel::Prerelease => {
+            if release_url != GITHUB_REPO {
+                bail!("the prerelease channel is not supported with a release mirror");
+            }
+            let bytes = download_url(&get_github_api_releases_url(), CommandOutput::Quiet)
+                .context("could not fetch the list of releases")?;
+            let releases: Vec<GithubRelease> =
+                serde_json::from_slice(&bytes).context("could not parse the list of releases")?;
+            releases
+                .into_iter()
+                .find(|x| !x.draft)
+                .map(|x| x.tag_name)
+                .ok_or_else(|| anyhow!("no releases available"))
+        }
+    }
+}
+
+/// Returns the GitHub API URL listing the releases of [`GITHUB_REPO`].
+fn get_github_api_releases_url() -> String {
+    let repo = GITHUB_REPO
+        .trim_start_matches("https://github.com/")
+        .trim_end_matches('/');
+    format!("https://api.github.com/repos/{}/releases", repo)
+}
+
+/// Remembers the update channel in the config.
+fn save_update_channel(channel: Channel) -> Result<(), Error> {
+    let mut config = Config::current();
+    let doc = Arc::make_mut(&mut config).doc_mut();
+    doc.entry("behavior").or_insert(toml_edit::table())["update-channel"] =
+        toml_edit::value(match channel {
+            Channel::Stable => "stable",
+            Channel::Prerelease => "prerelease",
+        });
+    config.save()
+}
+
+/// Returns the base URL that releases are downloaded from.
+///
+/// Mirrors are expected to follow the same layout as the GitHub releases.
+fn get_release_url(release_url: Option<&str>) -> Cow<'static, str> {
+    match release_url
+        .map(|x| x.to_string())
+        .or_else(|| env::var("RYE_RELEASE_URL").ok())
+    {
+        Some(url) => Cow::Owned(url.trim_end_matches('/').to_string()),
+        None => Cow::Borrowed(GITHUB_REPO),
+    }
+}
+
+/// Returns the download URL of an asset of a release, `version` can be `latest`.
+fn get_release_asset_url(release_url: &str, version: &str, asset: &str) -> String {
+    if version == "latest" {
+        format!("{release_url}/releases/latest/download/{asset}")
+    } else {
+        format!("{release_url}/releases/download/{version}/{asset}")
+    }
+}
+
+/// Replaces the current executable and updates the shims.
+///
+/// The current executable is backed up first and restored if the update fails.
+/// Returns [`ExeReplacement::Done`] with whether the shims were updated.  On
+/// Windows an executable that is in use cannot be replaced, the replacement
+/// is then scheduled for when rye exits and [`ExeReplacement::Scheduled`] is
+/// returned.
+fn update_exe_and_shims(new_exe: &Path) -> Result<ExeReplacement, Error> {
+    let app_dir = get_app_dir().canonicalize()?;
+    let current_exe = env::current_exe()?.canonicalize()?;
+    let shims = app_dir.join("shims");
+
+    let backup = backup_current_exe(&app_dir, &current_exe)
+        .context("could not back up current executable")?;
+
+    // once started, the replacement is finished even if interrupted
+    let _interrupt_guard = catch_interrupts();
+    if was_interrupted() {
+        bail!("update interrupted, the executable was not replaced");
+    }
+
+    let len = fs::metadata(new_exe).map_or(0, |x| x.len());
+    let mut progress = JsonProgress::new("replace", len);
+    progress.set_position(0);
+    match replace_exe_and_shims(new_exe, &current_exe, &shims) {
+        Ok(shims_updated) => {
+            progress.set_position(len);
+            // minimal installations do not keep backups around
+            if InstallManifest::load(&app_dir).map_or(false, |x| x.minimal) {
+                fs::remove_dir_all(backup.parent().unwrap()).ok();
+            }
+            Ok(ExeReplacement::Done { shims_updated })
+        }
+        #[cfg(windows)]
+        Err(err) if is_exe_in_use(&err) => {
+            schedule_replace_on_exit(&app_dir, new_exe, &current_exe)
+                .context("could not schedule update")?;
+            Ok(ExeReplacement::Scheduled)
+        }
+        Err(err) => {
+            if let Err(restore_err) = replace_exe_and_shims(&backup, &current_exe, &shims) {
+                warn!("could not restore previous version: {:#}", restore_err);
+            } else {
+                warn!("update failed, restored previous version");
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Makes sure the replaced executable can be run.
+///
+/// The new executable might not be executable depending on how it was
+/// downloaded.  On macOS a quarantine attribute would also make Gatekeeper
+/// prompt before running it.
+#[cfg(unix)]
+fn fix_exe_permissions(exe: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(exe, fs::Permissions::from_mode(0o755))
+        .context("could not make rye executable")?;
+
+    // the attribute is usually not there, so failing to remove it is fine.
+    if cfg!(target_os = "macos") {
+        Command::new("xattr")
+            .arg("-d")
+            .arg("com.apple.quarantine")
+            .arg(exe)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok();
+    }
+    Ok(())
+}
+
+/// Checks if replacing the executable failed because it's in use.
+#[cfg(windows)]
+fn is_exe_in_use(err: &Error) -> bool {
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    err.downcast_ref::<std::io::Error>()
+        .and_then(|x| x.raw_os_error())
+        .map_or(false, |x| {
+            x == ERROR_ACCESS_DENIED || x == ERROR_SHARING_VIOLATION
+        })
+}
+
+/// Replaces the executable once the current process exits.
+///
+/// The new executable is staged in the rye home and a batch script waits for
+/// this process to exit, swaps the executables and repairs the shims.
+#[cfg(windows)]
+fn schedule_replace_on_exit(
+    app_dir: &Path,
+    new_exe: &Path,
+    current_exe: &Path,
+) -> Result<(), Error> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let staging = app_dir.join("self-staging");
+    fs::create_dir_all(&staging)?;
+    let staged_exe = staging.join("rye.exe");
+    fs::copy(new_exe, &staged_exe)?;
+    let script = staging.join("replace.bat");
+    fs::write(
+        &script,
+        format!(
+            "@echo off\r\n\
+             :wait\r\n\
+             tasklist /FI \"PID eq {pid}\" 2>NUL | find \"{pid}\" >NUL\r\n\
+             if not errorlevel 1 (\r\n\
+             timeout /t 1 /nobreak >NUL\r\n\
+             goto wait\r\n\
+             )\r\n\
+             move /Y \"{staged}\" \"{current}\" >NUL\r\n\
+             \"{current}\" self fix-shims >NUL\r\n\
+             del \"%~f0\"\r\n",
+            pid = std::process::id(),
+            staged = staged_exe.display(),
+            current = current_exe.display(),
+        ),
+    )?;
+    Command::new("cmd")
+        .arg("/C")
+        .arg(&script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .spawn()?;
+    Ok(())
+}
+
+fn replace_exe_and_shims(new_exe: &Path, current_exe: &Path, shims: &Path) -> Result<bool, Error> {
+    self_replace::self_replace(new_exe)?;
+    #[cfg(unix)]
+    {
+        fix_exe_permissions(current_exe)?;
+    }
+
+    // if the shims have been created before (they really should have)
+    // we want to make sure that they point to the new executable now.
+    // for symlinks that probably is not necessary, but for hardlinks
+    // that's very important.
+    //
+    // shims that already point to this version (for instance after a forced
+    // reinstall) do not need to be touched.
+    if shims.is_dir() {
+        let version = get_rye_version(current_exe);
+        if version.is_none() || read_shims_version(shims) != version {
+            update_core_shims(shims, current_exe)?;
+        }
+        if let Some(version) = version {
+            write_shims_version(shims, &version)?;
+        }
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Copies the current executable into the backup folder.
+///
+/// Each version is backed up into `self-backups/<version>`.
+fn backup_current_exe(app_dir: &Path, current_exe: &Path) -> Result<PathBuf, Error> {
+    let backup_dir = app_dir.join("self-backups").join(env!("CARGO_PKG_VERSION"));
+    fs::create_dir_all(&backup_dir)?;
+    let backup = backup_dir.join(format!("rye{}", EXE_SUFFIX));
+    fs::copy(current_exe, &backup)?;
+    Ok(backup)
+}
+
+/// A backup of the executable made before an update.
+struct SelfBackup {
+    version: String,
+    dir: PathBuf,
+    exe: PathBuf,
+    size: u64,
+}
+
+/// Returns the backups of the executable, most recent first.
+fn get_self_backups(app_dir: &Path) -> Vec<SelfBackup> {
+    let dir = match app_dir.join("self-backups").read_dir() {
+        Ok(dir) => dir,
+        Err(_) => return Vec::new(),
+    };
+    let mut backups = dir
+        .filter_map(|x| x.ok())
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let exe = dir.join(format!("rye{}", EXE_SUFFIX));
+            let modified = fs::metadata(&exe).ok()?.modified().ok()?;
+            Some((
+                modified,
+                SelfBackup {
+                    version: entry.file_name().to_string_lossy().into_owned(),
+                    size: dir_size(&dir),
+                    dir,
+                    exe,
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+    backups.into_iter().map(|x| x.1).collect()
+}
+
+/// Returns the most recently created backup of the executable.
+fn get_latest_self_backup(app_dir: &Path) -> Option<PathBuf> {
+    get_self_backups(app_dir).into_iter().next().map(|x| x.exe)
+}
+
+fn backups(args: BackupsCommand) -> Result<(), Error> {
+    let app_dir = get_app_dir();
+    let backups = get_self_backups(app_dir);
+
+    if args.prune {
+        let mut reclaimed = 0;
+        for backup in backups.iter().skip(args.keep) {
+            fs::remove_dir_all(&backup.dir)
+                .with_context(|| format!("could not remove backup {}", backup.dir.display()))?;
+            echo!("Removed backup of rye {}", style(&backup.version).cyan());
+            reclaimed += backup.size;
+        }
+        if reclaimed > 0 {
+            echo!("Reclaimed {:.1} MB", reclaimed as f64 / (1024.0 * 1024.0));
+        } else {
+            echo!("Nothing to prune");
+        }
+        return Ok(());
+    }
+
+    if backups.is_empty() {
+        echo!("no backups");
+        return Ok(());
+    }
+    for (idx, backup) in backups.iter().enumerate() {
+        echo!(
+            "rye {} ({:.1} MB){}",
+            style(&backup.version).cyan(),
+            backup.size as f64 / (1024.0 * 1024.0),
+            if idx == 0 {
+                " (restored by --rollback)"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+fn rollback() -> Result<(), Error> {
+    let app_dir = get_app_dir().canonicalize()?;
+    let current_exe = env::current_exe()?.canonicalize()?;
+    let backup = match get_latest_self_backup(&app_dir) {
+        Some(backup) => backup,
+        None => bail!("no backup of a previous version available"),
+    };
+
+    echo!("Restoring {}", style(backup.display()).cyan());
+    replace_exe_and_shims(&backup, &current_exe, &app_dir.join("shims"))?;
+    echo!("Restored!");
+    echo!();
+    Command::new(current_exe).arg("--version").status()?;
+
+    Ok(())
+}
+
+fn install(args: InstallCommand) -> Result<(), Error> {
+    // an explicit home is treated as if RYE_HOME was exported which also makes
+    // the env file export it.
+    if let Some(ref home) = args.home {
+        env::set_var("RYE_HOME", env::current_dir()?.join(home));
+        crate::platform::init()?;
+        crate::config::load()?;
+    } else if args.system {
+        #[cfg(unix)]
+        {
+            env::set_var("RYE_HOME", SYSTEM_APP_DIR);
+            crate::platform::init()?;
+            crate::config::load()?;
+        }
+        #[cfg(windows)]
+        {
+            bail!("system installs are not supported on Windows, pass --home instead");
+        }
+    }
+
+    if args.force_reinstall && get_app_dir().is_dir() {
+        let app_dir = get_app_dir();
+        let question = format!(
+            "Remove the installation in {} before reinstalling?",
+            app_dir.display()
+        );
+        if !(args.yes || args.quiet)
+            && !confirm(
+                &question,
+                false,
+                args.prompt_timeout.map(Duration::from_secs),
+            )?
+        {
+            return Ok(());
+        }
+        let _lock = lock_app_dir(app_dir, APP_DIR_LOCK_WAIT)?;
+        remove_installation(app_dir, args.clean)?;
+    }
+
+    // the prompts would read from stdin as well
+    if args.toolchain.as_deref() == Some(Path::new("-"))
+        && !(args.yes || args.quiet || args.offline)
+    {
+        bail!("--toolchain - reads the toolchain from stdin and needs --yes");
+    }
+
+    perform_install(
+        if args.offline {
+            InstallMode::Offline
+        } else if args.yes || args.quiet {
+            InstallMode::NoPrompts
+        } else {
+            InstallMode::Default
+        },
+        &InstallOptions {
+            toolchain_path: args.toolchain.as_deref(),
+            toolchain_url: args.toolchain_url.as_deref(),
+            toolchain_arch: args.toolchain_arch.as_deref(),
+            toolchain_sha256: args.toolchain_sha256.as_deref(),
+            allow_arch_mismatch: args.allow_arch_mismatch,
+            no_self_venv: args.no_self_venv,
+            dry_run: args.dry_run,
+            force: args.force || args.force_reinstall,
+            portable: args.portable,
+            env_template: args.env_template.as_deref(),
+            shim_name: args.shim_name.as_deref(),
+            no_modify_path: args.no_modify_path,
+            default_yes: args.default_yes
+                || env::var("RYE_INSTALL_DEFAULT_YES").ok().as_deref() == Some("1"),
+            prompt_timeout: args.prompt_timeout.map(Duration::from_secs),
+            system: args.system,
+            no_telemetry: args.no_telemetry,
+            minimal: args.minimal,
+            output: CommandOutput::from_quiet_and_verbose(args.quiet, args.verbose),
+        },
+    )
+}
+
+/// Removes an installation for `--force-reinstall`.
+///
+/// The running executable is kept if it's one of the shims, the installer
+/// replaces it afterwards.
+fn remove_installation(app_dir: &Path, clean: bool) -> Result<(), Error> {
+    let current_exe = env::current_exe()?;
+    let manifest = InstallManifest::load(app_dir);
+    if let Ok(dir) = app_dir.join("shims").read_dir() {
+        for entry in dir.flatten() {
+            if !same_file::is_same_file(entry.path(), &current_exe).unwrap_or(false) {
+                fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+    remove_dir_all_if_exists(
+        manifest
+            .as_ref()
+            .and_then(|x| x.self_venv.as_deref())
+            .unwrap_or(&app_dir.join("self")),
+    )?;
+    remove_dir_all_if_exists(&app_dir.join("pip-tools"))?;
+    if clean {
+        remove_dir_all_if_exists(&app_dir.join("py"))?;
+    }
+    let env_file = manifest
+        .as_ref()
+        .and_then(|x| x.env_file.clone())
+        .unwrap_or_else(|| app_dir.join("env"));
+    fs::remove_file(env_file).ok();
+    fs::remove_file(InstallManifest::path(app_dir)).ok();
+    echo!("Removed the existing installation");
+    Ok(())
+}
+
+fn remove_dir_all_if_exists(path: &Path) -> Result<(), Error> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+fn relocate(args: RelocateCommand) -> Result<(), Error> {
+    let old_home = get_app_dir();
+    let new_home = env::current_dir()?.join(&args.new_home);
+    if !old_home.is_dir() {
+        bail!("rye is not installed at {}", old_home.display());
+    }
+    if new_home.starts_with(old_home) {
+        bail!("cannot relocate rye into its own home folder");
+    }
+    if new_home.is_dir() {
+        if new_home.read_dir()?.next().is_none() {
+            fs::remove_dir(&new_home)?;
+        } else if !args.force {
+            bail!(
+                "{} exists and is not empty (pass --force to move there anyway)",
+                new_home.display()
+            );
+        }
+    } else if new_home.exists() {
+        bail!("{} is not a folder", new_home.display());
+    }
+
+    // moving is the fastest option but it only works on the same file system.
+    // Otherwise copy everything over and remove the old home afterwards.
+    echo!(
+        "Moving {} to {}",
+        style(old_home.display()).cyan(),
+        style(new_home.display()).cyan()
+    );
+    let moved = !new_home.exists() && fs::rename(old_home, &new_home).is_ok();
+    if !moved {
+        fs::create_dir_all(&new_home)
+            .with_context(|| format!("could not create {}", new_home.display()))?;
+        copy_dir(
+            old_home,
+            new_home.as_path(),
+            &CopyDirOptions {
+                exclude: vec![old_home.join("self")],
+            },
+        )
+        .context("could not copy rye home")?;
+    }
+
+    // the internal virtualenv refers to the toolchains by absolute path
+    remove_dir_all_if_exists(&new_home.join("self"))?;
+
+    let new_home_str = new_home
+        .to_str()
+        .ok_or_else(|| anyhow!("non unicode path to new home"))?;
+    if cfg!(unix) {
+        fs::write(
+            new_home.join("env"),
+            render_env_file(None, true, new_home_str)?,
+        )
+        .context("could not write env file")?;
+    }
+    if let Some(mut manifest) = InstallManifest::load(&new_home) {
+        manifest.relocate(old_home, &new_home);
+        manifest.save(&new_home)?;
+    }
+    let shims = new_home.join("shims");
+    update_core_shims(&shims, &get_installed_rye_exe(&new_home))?;
+
+    if !moved {
+        let real_exe = env::current_exe()?.canonicalize()?;
+        let real_old_home = old_home.canonicalize()?;
+        if real_exe.strip_prefix(&real_old_home).is_ok() {
+            self_delete_outside_path(&real_old_home).ok();
+        }
+        fs::remove_dir_all(old_home)
+            .with_context(|| format!("could not remove {}", old_home.display()))?;
+    }
+
+    echo!("Relocated rye to {}", style(new_home.display()).cyan());
+    echo!();
+    echo!(
+        "Make sure to export {} and to update your {}.",
+        style(format!("RYE_HOME=\"{}\"", new_home.display())).cyan(),
+        style("PATH").cyan()
+    );
+    if new_home.join("tools").is_dir() {
+        echo!("Installed tools refer to the old location and need to be reinstalled.");
+    }
+    Ok(())
+}
+
+fn uninstall(args: UninstallCommand) -> Result<(), Error> {
+    // same as for install, an explicit home behaves like an exported RYE_HOME.
+    if let Some(ref home) = args.home {
+        env::set_var("RYE_HOME", env::current_dir()?.join(home));
+        crate::platform::init()?;
+        crate::config::load()?;
+    }
+    let yes = args.yes || args.quiet;
+    let _echo_guard = suppress_echo(args.quiet);
+
+    let app_dir = get_app_dir();
+    if !app_dir.is_dir() {
+        echo!(
+            "rye is not installed at {}",
+            style(app_dir.display()).cyan()
+        );
+        return Ok(());
+    }
+    let _lock = lock_app_dir(app_dir, APP_DIR_LOCK_WAIT)?;
+
+    if !yes && !args.keep_toolchains {
+        let venvs = get_registered_venvs();
+        if !venvs.is_empty() {
+            warn!("the following virtualenvs use rye toolchains and will stop working:");
+            for venv in &venvs {
+                echo!("  {}", style(venv.display()).cyan());
+            }
+            echo!("Pass --keep-toolchains to keep them working.");
+        }
+    }
+
+    let prompt_timeout = args.prompt_timeout.map(Duration::from_secs);
+    if !yes && !confirm("Do you want to uninstall rye?", false, prompt_timeout)? {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    let clean_path = args.clean_path
+        || (!yes
+            && confirm(
+                "Do you want to remove rye from your shell config?",
+                false,
+                prompt_timeout,
+            )?);
+    #[cfg(windows)]
+    {
+        if args.clean_path {
+            warn!("--clean-path is not supported on Windows");
+        }
+    }
+
+    let mut reclaimed = 0;
+    let real_exe = env::current_exe()?.canonicalize()?;
+    let real_app_dir = app_dir.canonicalize()?;
+
+    if !args.quiet {
+        let mut dirs = vec!["self", "pip-tools", "shims"];
+        if !args.keep_toolchains {
+            dirs.push("py");
+        }
+        reclaimed = dirs.into_iter().map(|x| dir_size(&app_dir.join(x))).sum();
+    }
+
+    // remove what the installer placed first.  Without a manifest (older
+    // installations) the default locations are used.
+    let manifest = InstallManifest::load(app_dir);
+    if let Some(ref manifest) = manifest {
+        for path in manifest.shims.iter().chain(Some(&manifest.binary)) {
+            fs::remove_file(path).ok();
+        }
+    }
+
+    // try to delete all remaining shims that can be found, for instance those
+    // of installed tools.  Ignore if deletes don't work.  The delete of the
+    // current executable for instance will fail on windows.
+    let shim_dir = app_dir.join("shims");
+    if let Ok(dir) = shim_dir.read_dir() {
+        for entry in dir.flatten() {
+            fs::remove_file(&entry.path()).ok();
+        }
+    }
+
+    remove_dir_all_if_exists(
+        manifest
+            .as_ref()
+            .and_then(|x| x.self_venv.as_deref())
+            .unwrap_or(&app_dir.join("self")),
+    )?;
+    remove_dir_all_if_exists(&app_dir.join("self-backups"))?;
+    // older versions placed all backups in a single folder
+    remove_dir_all_if_exists(&app_dir.join("self-backup"))?;
+    remove_dir_all_if_exists(&app_dir.join("pip-tools"))?;
+    remove_dir_all_if_exists(&app_dir.join("self-builds"))?;
+    remove_dir_all_if_exists(&app_dir.join("self-update-cache"))?;
+    if !args.keep_toolchains {
+        remove_dir_all_if_exists(&app_dir.join("py"))?;
+    }
+
+    // special deleting logic if we are placed in the app dir and the shim deletion
+    // did not succeed.  This is likely the case on windows where we then use the
+    // `self_delete` crate.
+    if real_exe.strip_prefix(&real_app_dir).is_ok() && real_exe.is_file() {
+        self_delete_outside_path(&real_app_dir)?;
+    }
+
+    // at this point the remaining shim folder should be deletable
+    remove_dir_all_if_exists(&app_dir.join("shims"))?;
+
+    // leave this empty behind in case someone sourced it.  The config also stays around.
+    let reason = args.reason.as_deref().map(|x| x.replace('\n', " "));
+    let env_file = manifest
+        .as_ref()
+        .and_then(|x| x.env_file.clone())
+        .unwrap_or_else(|| app_dir.join("env"));
+    if env_file.is_file() {
+        fs::write(
+            env_file,
+            match reason {
+                Some(ref reason) => format!("# rye was uninstalled: {}\n", reason),
+                None => String::new(),
+            },
+        )?;
+    }
+    if let (Some(manifest), Some(reason)) = (manifest, reason) {
+        let record = json!({
+            "version": manifest.version,
+            "uninstalled_at": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            "reason": reason,
+            "profile": if manifest.minimal { "minimal" } else { "default" },
+        });
+        fs::write(
+            app_dir.join("uninstall-record.json"),
+            serde_json::to_string_pretty(&record)?,
+        )
+        .context("could not write uninstall record")?;
+    }
+    fs::remove_file(InstallManifest::path(app_dir)).ok();
+
+    echo!("Done!");
+    if reclaimed > 0 {
+        echo!("Reclaimed {:.1} MB", reclaimed as f64 / (1024.0 * 1024.0));
+    }
+    echo!();
+
+    if args.keep_toolchains {
+        echo!(
+            "Toolchains were kept in {}",
+            style(app_dir.join("py").display()).cyan()
+        );
+        echo!();
+    }
+
+    let (_, rye_home) = get_env_file_home();
+    #[cfg(unix)]
+    {
+        let mut cleaned = false;
+        if clean_path {
+            for path in remove_env_sourcing(&rye_home)? {
+                echo!("Removed sourcing of env file from {}", path.display());
+                cleaned = true;
+            }
+            if is_fish() && remove_fish_user_path(&app_dir.join("shims"))? {
+                echo!("Removed shims from fish_user_paths");
+                cleaned = true;
+            }
+        }
+        if !cleaned {
+            echo!(
+                "Don't forget to remove the sourcing of {} from your shell config.",
+                Path::new(&rye_home as &str).join("env").display()
+            );
+        }
+    }
+    #[cfg(windows)]
+    {
+        echo!(
+            "Don't forget to remove {} from your PATH",
+            Path::new(&rye_home as &str).join("shims").display()
+        )
+    }
+
+    Ok(())
+}
+
+/// Returns the total size of all files in a folder in bytes.
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Removes the lines sourcing the env file from the shell config files.
+///
+/// Returns the paths of the files that were changed.
+#[cfg(unix)]
+fn remove_env_sourcing(rye_home: &str) -> Result<Vec<PathBuf>, Error> {
+    let home = match simple_home_dir::home_dir() {
+        Some(home) => home,
+        None => return Ok(Vec::new()),
+    };
+    let env_files = [
+        format!("{}/env", rye_home),
+        get_app_dir().join("env").display().to_string(),
+    ];
+
+    let mut rv = Vec::new();
+    for filename in [
+        ".profile",
+        ".bashrc",
+        ".bash_profile",
+        ".zshrc",
+        ".zprofile",
+    ] {
+        let path = home.join(filename);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+        let mut changed = false;
+        let new_contents = contents
+            .split_inclusive('\n')
+            .filter(|line| {
+                let sources_env = is_env_sourcing_line(line, &env_files);
+                changed |= sources_env;
+                !sources_env
+            })
+            .collect::<String>();
+        if changed {
+            fs::write(&path, new_contents)
+                .with_context(|| format!("failed to update {}", path.display()))?;
+            rv.push(path);
+        }
+    }
+    Ok(rv)
+}
+
+/// Checks if a shell config line sources one of the given env files.
+#[cfg(unix)]
+fn is_env_sourcing_line(line: &str, env_files: &[String]) -> bool {
+    let line = line.trim();
+    let arg = match line
+        .strip_prefix("source ")
+        .or_else(|| line.strip_prefix(". "))
+    {
+        Some(arg) => arg.trim().trim_matches(['"', '\'']),
+        None => return false,
+    };
+    env_files.iter().any(|x| x == arg)
+}
+
+/// Removes the shims folder from the fish user paths.
+#[cfg(unix)]
+fn remove_fish_user_path(shims: &Path) -> Result<bool, Error> {
+    let status = Command::new("fish")
+        .arg("-c")
+        .arg(FISH_REMOVE_USER_PATH)
+        .env("RYE_SHIMS", shims)
+        .status()
+        .context("unable to invoke fish")?;
+    Ok(status.success())
+}
+
+#[cfg(unix)]
+fn is_fish() -> bool {
+    use whattheshell::Shell;
+    Shell::infer().map_or(false, |x| matches!(x, Shell::Fish))
+}
+
+/// How the env file is hooked into the shell.
+#[cfg(unix)]
+enum ShellSetup {
+    /// fish uses `fish_user_paths` instead of the env file.
+    Fish,
+    /// The env file is sourced from an rc file in the home folder.
+    Source {
+        rc_file: &'static str,
+        command: &'static str,
+    },
+}
+
+/// Figures out how the user's shell should source the env file.
+#[cfg(unix)]
+fn detect_shell_setup() -> ShellSetup {
+    use whattheshell::Shell;
+    let profile = ShellSetup::Source {
+        rc_file: ".profile",
+        command: ".",
+    };
+    Shell::infer().map_or(profile, |shell| match shell {
+        Shell::Fish => ShellSetup::Fish,
+        Shell::Zsh => ShellSetup::Source {
+            rc_file: ".zprofile",
+            command: "source",
+        },
+        // bash does not read .profile if a .bash_profile exists
+        Shell::Bash => ShellSetup::Source {
+            rc_file: if simple_home_dir::home_dir()
+                .map_or(false, |x| x.join(".bash_profile").is_file())
+            {
+                ".bash_profile"
+            } else {
+                ".profile"
+            },
+            command: "source",
+        },
+        _ => ShellSetup::Source {
+            rc_file: ".profile",
+            command: ".",
+        },
+    })
+}
+
+/// Renders the unix env file from the default or a custom template.
+fn render_env_file(
+    template: Option<&Path>,
+    custom_home: bool,
+    rye_home: &str,
+) -> Result<String, Error> {
+    let template = match template {
+        Some(path) => Cow::Owned(
+            fs::read_to_string(path)
+                .with_context(|| format!("could not read env template {}", path.display()))?,
+        ),
+        None => Cow::Borrowed(UNIX_ENV_FILE),
+    };
+    let rv = Environment::new()
+        .render_named_str("env", &template, context! { custom_home, rye_home })
+        .context("could not render env template")?;
+    let shims = format!("{}/shims", rye_home);
+    if !rv
+        .lines()
+        .any(|line| line.contains("PATH") && line.contains(&shims))
+    {
+        bail!("env template does not add {} to PATH", shims);
+    }
+    Ok(rv)
+}
+
+/// Returns the version reported by a rye executable.
+fn get_rye_version(exe: &Path) -> Option<String> {
+    let rv = Command::new(exe)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    if !rv.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&rv.stdout)
+        .lines()
+        .next()
+        .and_then(|x| x.strip_prefix("rye "))
+        .map(|x| x.trim().to_string())
+}
+
+/// Makes an installation usable by all users.
+///
+/// Folders and executables become world executable and everything becomes
+/// world readable.
+#[cfg(unix)]
+fn make_readable_for_all(app_dir: &Path) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    for entry in walkdir::WalkDir::new(app_dir) {
+        let entry = entry?;
+        // symlinks share the permissions of their targets
+        if entry.path_is_symlink() {
+            continue;
+        }
+        let mut perms = entry.metadata()?.permissions();
+        let mode = perms.mode();
+        let mode = if entry.file_type().is_dir() || mode & 0o100 != 0 {
+            mode | 0o755
+        } else {
+            mode | 0o644
+        };
+        perms.set_mode(mode);
+        fs::set_permissions(entry.path(), perms)
+            .with_context(|| format!("could not set permissions of {}", entry.path().display()))?;
+    }
+    Ok(())
+}
+
+/// Returns the rye home as it's referenced by the env file.
+///
+/// `$HOME/.rye` is preferred over the expanded path, if not overridden.  A
+/// custom home, which includes one in the XDG data directory, is exported
+/// by the env file so that rye finds it no matter how it was configured.
+fn get_env_file_home() -> (bool, Cow<'static, str>) {
+    if let Ok(rye_home) = env::var("RYE_HOME") {
+        (true, Cow::Owned(rye_home))
+    } else if get_xdg_home().is_some() {
+        (true, Cow::Owned(get_app_dir().display().to_string()))
+    } else {
+        (false, Cow::Borrowed(DEFAULT_HOME))
+    }
+}
+
+/// Checks if this process is an x86_64 binary translated by Rosetta.
+fn is_rosetta_translated() -> bool {
+    if !cfg!(all(target_os = "macos", target_arch = "x86_64")) {
+        return false;
+    }
+    // the sysctl only exists on Apple Silicon and is 1 for translated processes
+    Command::new("sysctl")
+        .arg("-in")
+        .arg("sysctl.proc_translated")
+        .stderr(Stdio::null())
+        .output()
+        .map_or(false, |output| {
+            String::from_utf8_lossy(&output.stdout).trim() == "1"
+        })
+}
+
+/// Fails if a toolchain is not built for the given architecture, or for the
+/// architecture of this machine if none is given.
+///
+/// Toolchains that cannot be inspected (for instance scripts) are accepted.
+fn check_toolchain_arch(path: &Path, arch: Option<&str>) -> Result<(), Error> {
+    if let Some(archs) = get_binary_archs(path) {
+        if let Some(arch) = arch {
+            if !archs.iter().any(|x| *x == arch) {
+                bail!(
+                    "toolchain at {} is built for {} but --toolchain-arch is {}. Pass \
+                     --allow-arch-mismatch if this is deliberate.",
+                    path.display(),
+                    archs.join(", "),
+                    arch
+                );
+            }
+        } else if !archs.contains(&ARCH) {
+            bail!(
+                "toolchain at {} is built for {} but this machine is {}. Pass \
+                 --allow-arch-mismatch if this is deliberate (for instance with Rosetta).",
+                path.display(),
+                archs.join(", "),
+                ARCH
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fails early if the rye home folder cannot be written to.
+fn check_writable(app_dir: &Path) -> Result<(), Error> {
+    let rv = fs::create_dir_all(app_dir)
+        .and_then(|_| tempfile::tempfile_in(app_dir))
+        .map(|_| ());
+    if let Err(err) = rv {
+        bail!(
+            "cannot write to {} ({}). Set the RYE_HOME environment variable to \
+             install rye into a different folder.",
+            app_dir.display(),
+            err
+        );
+    }
+    Ok(())
+}
+
+/// Downloads and unpacks a toolchain archive.
+///
+/// The archive is expected to be laid out like the python-build-standalone
+/// builds.  Returns the path to the interpreter in it.
+fn download_toolchain(
+    app_dir: &Path,
+    url: &str,
+    sha256: Option<&str>,
+    output: CommandOutput,
+) -> Result<PathBuf, Error> {
+    echo!(
+        "Downloading toolchain from {}",
+        style(redact_url(url)).cyan()
+    );
+    let bytes = download_url(url, output)?;
+    if let Some(sha256) = sha256 {
+        echo!("Checking checksum");
+        check_checksum(&bytes, sha256)
+            .with_context(|| format!("hash check of {} failed", redact_url(url)))?;
+    } else {
+        echo!("Checksum check skipped (no --toolchain-sha256 given)");
+    }
+
+    // the registered toolchain links into this folder, so it has to stay
+    let key = hex::encode(Sha256::digest(url.as_bytes()));
+    let target = app_dir.join("py").join(format!("download-{}", &key[..16]));
+    unpack_toolchain(&bytes, &target)
+        .with_context(|| format!("could not unpack toolchain from {}", redact_url(url)))
+}
+
+/// Reads a toolchain archive from stdin and unpacks it.
+///
+/// Returns the path to the interpreter in it.
+fn read_toolchain_from_stdin(app_dir: &Path, sha256: Option<&str>) -> Result<PathBuf, Error> {
+    if std::io::stdin().is_terminal() {
+        bail!("--toolchain - expects a toolchain archive to be piped to stdin");
+    }
+    echo!("Reading toolchain archive from stdin");
+    let mut bytes = Vec::new();
+    std::io::stdin()
+        .lock()
+        .read_to_end(&mut bytes)
+        .context("could not read toolchain from stdin")?;
+    if let Some(sha256) = sha256 {
+        echo!("Checking checksum");
+        check_checksum(&bytes, sha256).context("hash check of toolchain from stdin failed")?;
+    } else {
+        echo!("Checksum check skipped (no --toolchain-sha256 given)");
+    }
+
+    let key = hex::encode(Sha256::digest(&bytes));
+    let target = app_dir.join("py").join(format!("stdin-{}", &key[..16]));
+    unpack_toolchain(&bytes, &target).context("could not unpack toolchain from stdin")
+}
+
+/// Unpacks a toolchain archive into `target` and returns its interpreter.
+fn unpack_toolchain(bytes: &[u8], target: &Path) -> Result<PathBuf, Error> {
+    remove_dir_all_if_exists(target)?;
+    fs::create_dir_all(target)?;
+    unpack_archive(bytes, target, 1)?;
+
+    let candidates: &[&str] = if cfg!(windows) {
+        &["install/python.exe", "python.exe"]
+    } else {
+        &["install/bin/python3", "bin/python3"]
+    };
+    candidates
+        .iter()
+        .map(|x| target.join(x))
+        .find(|x| x.is_file())
+        .ok_or_else(|| anyhow!("no python interpreter found in toolchain archive"))
+}
+
+/// Warns if the installed executable does not work or is shadowed.
+fn verify_installed_exe(target: &Path) {
+    let expected = env!("CARGO_PKG_VERSION");
+    match get_rye_version(target) {
+        Some(version) if version == expected => {}
+        Some(version) => {
+            echo!();
+            warn!(
+                "the installed rye reports version {} instead of {}",
+                version, expected
+            );
+        }
+        None => {
+            echo!();
+            warn!("could not run the installed rye at {}", target.display());
+        }
+    }
+
+    if let Ok(found) = which::which(target.file_name().unwrap_or_default()) {
+        if found.canonicalize().ok() != target.canonicalize().ok() {
+            echo!();
+            warn!(
+                "{} on PATH shadows the installed rye at {}",
+                found.display(),
+                target.display()
+            );
+        }
+    }
+}
+
+/// Asks which downloadable toolchain rye should use internally.
+///
+/// Returns `None` if the default should be used.
+fn pick_self_toolchain() -> Result<Option<PythonVersion>, Error> {
+    let mut versions = iter_downloadable(OS, ARCH)
+        .filter(is_self_compatible_toolchain)
+        .collect::<Vec<_>>();
+    versions.sort();
+    versions.reverse();
+    // only offer the latest patch release of every minor version
+    versions.dedup_by_key(|x| (x.major, x.minor));
+    if versions.is_empty() {
+        return Ok(None);
+    }
+
+    let items = Some("Default".to_string())
+        .into_iter()
+        .chain(versions.iter().map(|x| x.to_string()))
+        .collect::<Vec<_>>();
+    let picked = dialoguer::Select::new()
+        .with_prompt("Which Python should rye use internally?")
+        .items(&items)
+        .default(0)
+        .interact()?;
+    Ok(picked.checked_sub(1).map(|idx| versions.swap_remove(idx)))
+}
+
+/// Persists that telemetry is off unless it was explicitly turned on.
+///
+/// With `force` it's turned off even if it was turned on.
+fn save_telemetry_default(force: bool) -> Result<(), Error> {
+    let mut config = Config::current();
+    let doc = Arc::make_mut(&mut config).doc_mut();
+    let behavior = doc.entry("behavior").or_insert(toml_edit::table());
+    if force || behavior.get("telemetry").is_none() {
+        behavior["telemetry"] = toml_edit::value(false);
+        config.save()?;
+    }
+    Ok(())
+}
+
+/// Remembers the python for the internals in the config.
+fn save_self_python(version: &PythonVersion) -> Result<(), Error> {
+    let mut config = Config::current();
+    let doc = Arc::make_mut(&mut config).doc_mut();
+    doc.entry("behavior").or_insert(toml_edit::table())["self-python"] =
+        toml_edit::value(version.to_string());
+    config.save()
+}
+
+/// Optional log of the installation steps enabled with `RYE_INSTALL_LOG`.
+///
+/// Every step is appended as a JSON object on its own line.  Failing to write
+/// the log never fails the installation.
+struct InstallLog {
+    file: Option<fs::File>,
+}
+
+impl InstallLog {
+    fn open() -> InstallLog {
+        InstallLog {
+            file: env::var_os("RYE_INSTALL_LOG").and_then(|path| {
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .ok()
+            }),
+        }
+    }
+
+    /// Records the outcome of a step.
+    fn record(&mut self, step: &str, outcome: &str, detail: Option<&str>) {
+        if let Some(ref mut file) = self.file {
+            let ts = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |x| x.as_secs_f64());
+            let event = json!({
+                "ts": ts,
+                "step": step,
+                "outcome": outcome,
+                "detail": detail,
+            });
+            writeln!(file, "{}", event).ok();
+        }
+    }
+
+    /// Records if a step succeeded or failed.
+    fn result<T>(&mut self, step: &str, rv: &Result<T, Error>, detail: &str) {
+        match rv {
+            Ok(_) => self.record(step, "ok", Some(detail)),
+            Err(err) => self.record(step, &format!("error: {:#}", err), Some(detail)),
+        }
+    }
+}
+
+/// Checks if an env file contains nothing but comments.
+fn is_blank_env_file(contents: &str) -> bool {
+    contents.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+/// Prints a line based diff between two texts.
+fn print_line_diff(old: &str, new: &str) {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // longest common subsequence, the inputs are small enough for this
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            echo!("  {}", old[i]);
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            echo!("{}", style(format!("+ {}", new[j])).green());
+            j += 1;
+        } else {
+            echo!("{}", style(format!("- {}", old[i])).red());
+            i += 1;
+        }
+    }
+}
+
+/// Checks if a name can be used for the rye executable in the shims folder.
+///
+/// The name has to start with `rye` so that it cannot take the place of a
+/// shim.
+fn is_valid_shim_name(name: &str) -> bool {
+    name.starts_with("rye")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Returns the path of the installed rye executable.
+///
+/// This honors the name the executable was installed under.
+pub fn get_installed_rye_exe(app_dir: &Path) -> PathBuf {
+    InstallManifest::load(app_dir)
+        .map(|x| x.binary)
+        .filter(|x| x.is_file())
+        .unwrap_or_else(|| {
+            app_dir
+                .join("shims")
+                .join("rye")
+                .with_extension(EXE_EXTENSION)
+        })
+}
+
+/// Checks if a folder is on the PATH of the current process.
+fn is_on_path(folder: &Path) -> bool {
+    env::split_paths(&env::var_os("PATH").unwrap_or_default())
+        .any(|x| same_file::is_same_file(x, folder).unwrap_or(false))
+}
+
+/// Prints how to add the shims folder to the PATH on Windows.
+#[cfg(windows)]
+fn print_windows_path_advice(shims: &Path) {
+    let shims = shims.display();
+    let persistent = format!(
+        "[Environment]::SetEnvironmentVariable(\"PATH\", \"{};\" + \
+         [Environment]::GetEnvironmentVariable(\"PATH\", \"User\"), \"User\")",
+        shims
+    );
+    echo!("Note: You need to manually add {} to your PATH.", shims);
+    if is_powershell() {
+        echo!("For the current session run:");
+        echo!();
+        echo!("    $env:PATH = \"{};$env:PATH\"", shims);
+        echo!();
+        echo!("To make it permanent run:");
+    } else {
+        echo!("For the current session run:");
+        echo!();
+        echo!("    set PATH={};%PATH%", shims);
+        echo!();
+        echo!("To make it permanent run this in PowerShell:");
+    }
+    echo!();
+    echo!("    {}", persistent);
+    echo!();
+}
+
+/// Guesses if rye was started from PowerShell.
+///
+/// PowerShell adds the module folders of the user to `PSModulePath` which
+/// otherwise only contains the system wide ones.
+#[cfg(windows)]
+fn is_powershell() -> bool {
+    env::var_os("PSModulePath").map_or(false, |x| split_paths(&x).count() >= 3)
+}
+
+/// Checks if a folder is on the user's PATH in the registry.
+#[cfg(windows)]
+fn is_on_user_path(folder: &Path) -> Result<bool, Error> {
+    let path = read_user_path()?;
+    let folder = folder.to_string_lossy();
+    let folder = folder.trim_end_matches('\\');
+    Ok(path
+        .split(';')
+        .any(|x| x.trim_end_matches('\\').eq_ignore_ascii_case(folder)))
+}
+
+/// Reads the PATH from `HKCU\Environment`.
+#[cfg(windows)]
+fn read_user_path() -> Result<String, Error> {
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ};
+    use winreg::RegKey;
+
+    let env = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags("Environment", KEY_READ)
+        .context("failed to open user environment")?;
+    Ok(env.get_value::<String, _>("Path").unwrap_or_default())
+}
+
+/// Prepends a folder to the user's PATH in the registry.
+///
+/// Running shells are told about the change by broadcasting `WM_SETTINGCHANGE`.
+/// Folders already on the PATH are not added again.
+#[cfg(windows)]
+fn add_to_user_path(folder: &Path) -> Result<(), Error> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::minwindef::{LPARAM, WPARAM};
+    use winapi::um::winuser::{
+        SendMessageTimeoutA, HWND_BROADCAST, SMTO_ABORTIFHUNG, WM_SETTINGCHANGE,
+    };
+    use winreg::enums::{RegType, HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+    use winreg::{RegKey, RegValue};
+
+    if is_on_user_path(folder)? {
+        return Ok(());
+    }
+
+    let path = read_user_path()?;
+    let mut new_path = folder.as_os_str().to_owned();
+    if !path.is_empty() {
+        new_path.push(";");
+        new_path.push(&path);
+    }
+
+    // PATH is stored as REG_EXPAND_SZ so that entries like %USERPROFILE% keep working
+    let bytes = new_path
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .flat_map(|x| x.to_le_bytes())
+        .collect();
+    RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)
+        .context("failed to open user environment")?
+        .set_raw_value(
+            "Path",
+            &RegValue {
+                bytes,
+                vtype: RegType::REG_EXPAND_SZ,
+            },
+        )
+        .context("failed to update PATH")?;
+
+    unsafe {
+        SendMessageTimeoutA(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0 as WPARAM,
+            "Environment\0".as_ptr() as LPARAM,
+            SMTO_ABORTIFHUNG,
+            5000,
+            std::ptr::null_mut(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Asks a yes or no question.
+///
+/// Unless `default_yes` is set an explicit answer is required.  Without a
+/// terminal this fails right away instead of waiting forever, and with a
+/// timeout the question is answered with no once it expires.
+fn confirm(prompt: &str, default_yes: bool, timeout: Option<Duration>) -> Result<bool, Error> {
+    // the answer is read from stdin, so that is what has to be interactive
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "cannot ask \"{}\" without a terminal. Pass --yes to skip the prompts.",
+            prompt
+        );
+    }
+
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => {
+            let mut question = dialoguer::Confirm::new();
+            question.with_prompt(prompt);
+            if default_yes {
+                question.default(true);
+            }
+            return Ok(question.interact()?);
+        }
+    };
+
+    read_answer(prompt, default_yes, timeout, &stdin_lines().lock().unwrap())
+}
+
+/// Returns the lines read from stdin.
+///
+/// dialoguer cannot time out, so lines are read instead.  Unlike reading
+/// single keys this leaves the terminal alone.  The reading thread keeps
+/// holding the stdin lock after a prompt timed out, so a single thread is
+/// shared by all prompts of the process.
+fn stdin_lines() -> &'static Mutex<Receiver<std::io::Result<String>>> {
+    static LINES: Lazy<Mutex<Receiver<std::io::Result<String>>>> = Lazy::new(|| {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(rx)
+    });
+    &LINES
+}
+
+/// Asks a yes or no question answered by the lines from `lines`.
+fn read_answer(
+    prompt: &str,
+    default_yes: bool,
+    timeout: Duration,
+    lines: &Receiver<std::io::Result<String>>,
+) -> Result<bool, Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        eprint!(
+            "{} {} ",
+            prompt,
+            style(if default_yes { "[Y/n]" } else { "[y/n]" }).dim()
+        );
+        let line = match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => {
+                elog!();
+                elog!("No answer within {} seconds", timeout.as_secs());
+                return Ok(false);
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(false),
+        };
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            "" if default_yes => return Ok(true),
+            _ => {}
+        }
+    }
+}
+
+fn perform_install(mode: InstallMode, opts: &InstallOptions) -> Result<(), Error> {
+    let exe = env::current_exe()?;
+    let app_dir = get_app_dir();
+    let shims = app_dir.join("shims");
+    let shim_name = opts.shim_name.unwrap_or("rye");
+    if !is_valid_shim_name(shim_name) {
+        bail!(
+            "invalid shim name '{}': it must start with 'rye' and only contain \
+             letters, digits, dashes and underscores",
+            shim_name
+        );
+    }
+    let target = shims.join(shim_name).with_extension(EXE_EXTENSION);
+    let _lock = if !opts.dry_run {
+        check_writable(app_dir)?;
+        Some(lock_app_dir(app_dir, APP_DIR_LOCK_WAIT)?)
+    } else {
+        None
+    };
+
+    // in quiet mode only errors and the final result are shown
+    let echo_guard = suppress_echo(opts.output == CommandOutput::Quiet);
+    let mut log = InstallLog::open();
+
+    echo!("{}", style("Welcome to Rye!").bold());
+    log.record("banner", "ok", Some(env!("CARGO_PKG_VERSION")));
+
+    if matches!(mode, InstallMode::AutoInstall) {
+        echo!();
+        echo!("Rye has detected that it's not installed on this computer yet and");
+        echo!("automatically started the installer for you.  For more information");
+        echo!(
+            "read {}",
+            style("https://rye-up.com/guide/installation/").yellow()
+        );
+    }
+
+    echo!();
+    echo!(
+        "This installer will install rye to {}",
+        style(app_dir.display()).cyan()
+    );
+    echo!(
+        "This path can be changed by exporting the {} environment variable.",
+        style("RYE_HOME").cyan()
+    );
+    echo!();
+    echo!("{}", style("Details:").bold());
+    echo!("  Rye Version: {}", style(env!("CARGO_PKG_VERSION")).cyan());
+    echo!("  Platform: {} ({})", style(OS).cyan(), style(ARCH).cyan());
+    echo!(
+        "  Telemetry: {}",
+        style(
+            if !opts.no_telemetry && Config::current().telemetry_enabled() {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        )
+        .cyan()
+    );
+    if opts.minimal {
+        echo!("  Profile: {}", style("minimal").cyan());
+    }
+
+    let mut reinstall_prompt = None;
+    let mut downgrade_from = None;
+    if let Some(installed_version) = target.is_file().then(|| get_rye_version(&target)).flatten() {
+        let new_version = env!("CARGO_PKG_VERSION");
+        let newer_installed = match (
+            installed_version.parse::<Version>(),
+            new_version.parse::<Version>(),
+        ) {
+            (Ok(installed), Ok(new)) => installed >= new,
+            _ => installed_version == new_version,
+        };
+        if !newer_installed {
+            echo!(
+                "  Upgrading from {} to {}",
+                style(&installed_version).cyan(),
+                style(new_version).cyan()
+            );
+        } else if !opts.force {
+            reinstall_prompt = Some(if installed_version == new_version {
+                format!(
+                    "Rye {} is already installed.  Reinstall?",
+                    installed_version
+                )
+            } else {
+                downgrade_from = Some(installed_version.clone());
+                format!(
+                    "Rye {} is already installed.  Downgrade to {}?",
+                    installed_version, new_version
+                )
+            });
+        }
+    }
+
+    if cfg!(windows) && !symlinks_supported() {
+        echo!();
+        warn!("your Windows configuration does not support symlinks.");
+        echo!();
+        echo!("It's strongly recommended that you enable developer mode in Windows to");
+        echo!("enable symlinks.  You need to enable this before continuing the setup.");
+        echo!(
+            "Learn more at {}",
+            style("https://rye-up.com/guide/faq/#windows-developer-mode").yellow()
+        );
+    }
+
+    if is_rosetta_translated() {
+        echo!();
+        warn!("this is the x86_64 build of rye running under Rosetta.");
+        echo!();
+        echo!("Toolchains would be installed for x86_64 instead of for your Apple");
+        echo!("Silicon Mac.  It's strongly recommended to use the native build instead:");
         echo!(
-            "Registering toolchain at {}",
-            style(toolchain_path.display()).cyan()
+            "{}",
+            style(redact_url(&get_release_asset_url(
+                &get_release_url(None),
+                "latest",
+                "rye-aarch64-macos.gz"
+            )))
+            .yellow()
         );
-        let version = register_toolchain(toolchain_path, None, |ver| {
-            if ver.name != "cpython" {
-                bail!("Only cpython toolchains are allowed, got '{}'", ver.name);
-            } else if !is_self_compatible_toolchain(ver) {
+    }
+
+    // write an env file we can source later.  This is rendered ahead of time
+    // so that a broken custom template does not leave a partial installation.
+    let (custom_home, rye_home) = get_env_file_home();
+    let env_contents = if cfg!(unix) {
+        Some(render_env_file(opts.env_template, custom_home, &rye_home)?)
+    } else {
+        None
+    };
+
+    echo!();
+    if let Some(prompt) = reinstall_prompt {
+        // --yes agrees to a reinstall, but not to a downgrade
+        if matches!(mode, InstallMode::NoPrompts | InstallMode::Offline) {
+            if let (Some(installed_version), false) = (downgrade_from, opts.dry_run) {
                 bail!(
-                    "Toolchain {} is not version compatible for internal use.",
-                    ver
+                    "rye {} is already installed; pass --force to downgrade to {}",
+                    installed_version,
+                    env!("CARGO_PKG_VERSION")
                 );
             }
+        } else if !opts.dry_run && !confirm(&prompt, false, opts.prompt_timeout)? {
+            log.record("prompt", "cancelled", Some("reinstall"));
+            elog!("Installation cancelled!");
+            return Err(QuietExit(1).into());
+        }
+    }
+    if matches!(mode, InstallMode::NoPrompts | InstallMode::Offline) || opts.dry_run {
+        log.record("prompt", "skipped", None);
+    } else if confirm("Continue?", opts.default_yes, opts.prompt_timeout)? {
+        log.record("prompt", "confirmed", None);
+    } else {
+        log.record("prompt", "cancelled", None);
+        elog!("Installation cancelled!");
+        return Err(QuietExit(1).into());
+    }
+
+    // bootstrapping the internals downloads a toolchain, so fail before
+    // changing anything if that is bound to fail.  Nothing is downloaded if
+    // a toolchain is given or the internals are already current.
+    if !opts.dry_run
+        && !matches!(mode, InstallMode::Offline)
+        && !opts.no_self_venv
+        && opts.toolchain_path.is_none()
+        && opts.toolchain_url.is_none()
+        && !is_self_venv_current()
+    {
+        let rv = check_toolchain_host_reachable();
+        log.result("check network", &rv, "");
+        if let Err(err) = rv {
+            bail!(
+                "{:#}. Check your network and proxy settings, or pass --offline together \
+                 with --toolchain to install without network access.",
+                err
+            );
+        }
+    }
+
+    // place executable in rye home folder
+    if opts.dry_run {
+        echo!("Would create shim folder {}", style(shims.display()).cyan());
+        echo!("Would install binary to {}", style(target.display()).cyan());
+    } else {
+        fs::create_dir_all(&shims).ok();
+        let rv = (|| -> Result<(), Error> {
+            if target.is_file() {
+                fs::remove_file(&target)?;
+            }
+            fs::copy(&exe, &target)?;
             Ok(())
-        })?;
-        echo!("Registered toolchain as {}", style(version).cyan());
+        })();
+        log.result("copy binary", &rv, &target.display().to_string());
+        rv?;
+        echo!("Installed binary to {}", style(target.display()).cyan());
     }
 
-    // Ensure internals next
-    let self_path = ensure_self_venv(CommandOutput::Normal)?;
-    echo!(
-        "Updated self-python installation at {}",
-        style(self_path.display()).cyan()
-    );
+    // portable installations are marked so that all later shim updates also
+    // write relative shims and rye finds its home folder from the executable.
+    if opts.portable {
+        if opts.dry_run {
+            echo!("Would mark installation as portable");
+        } else {
+            fs::write(shims.join(PORTABLE_MARKER), "")
+                .context("failed to mark installation as portable")?;
+        }
+    } else if !opts.dry_run {
+        fs::remove_file(shims.join(PORTABLE_MARKER)).ok();
+    }
 
-    #[cfg(unix)]
+    let mut manifest = InstallManifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        installed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs()),
+        binary: target.clone(),
+        minimal: opts.minimal,
+        ..Default::default()
+    };
+
+    // write the env file we can source later.
+    if let Some(env_contents) = env_contents {
+        let env_file = app_dir.join("env");
+        if opts.dry_run {
+            echo!(
+                "Would write env file to {}",
+                style(env_file.display()).cyan()
+            );
+        } else {
+            // the env file might have been customized, so be careful about
+            // replacing it with different contents.  An empty env file (maybe
+            // with a comment) is what uninstall leaves behind and is always
+            // rewritten.
+            let old_contents = fs::read_to_string(&env_file).ok();
+            let overwrite = match old_contents {
+                Some(ref old_contents)
+                    if !is_blank_env_file(old_contents) && old_contents != &env_contents =>
+                {
+                    echo!();
+                    echo!(
+                        "The env file {} has changed:",
+                        style(env_file.display()).cyan()
+                    );
+                    print_line_diff(old_contents, &env_contents);
+                    !matches!(mode, InstallMode::Default)
+                        || confirm("Overwrite the env file?", false, opts.prompt_timeout)?
+                }
+                _ => true,
+            };
+            if overwrite {
+                let rv = fs::write(&env_file, env_contents).map_err(Error::from);
+                log.result("write env", &rv, &env_file.display().to_string());
+                rv?;
+            } else {
+                log.record("write env", "kept", Some(&env_file.display().to_string()));
+                echo!("Kept the existing env file");
+            }
+            manifest.env_file = Some(env_file);
+        }
+    }
+
+    // A toolchain archive is downloaded (or read from stdin) and unpacked
+    // first, the interpreter in it is then registered like a local one.
+    let downloaded_toolchain;
+    let mut toolchain_sha256 = opts.toolchain_sha256;
+    let toolchain_path = match opts.toolchain_url {
+        Some(url) if opts.dry_run => {
+            echo!(
+                "Would download toolchain from {}",
+                style(redact_url(url)).cyan()
+            );
+            None
+        }
+        Some(url) => {
+            let rv = download_toolchain(app_dir, url, opts.toolchain_sha256, opts.output);
+            log.result("download toolchain", &rv, &redact_url(url));
+            downloaded_toolchain = rv?;
+            // the checksum was already checked against the archive
+            toolchain_sha256 = None;
+            Some(downloaded_toolchain.as_path())
+        }
+        None if opts.toolchain_path == Some(Path::new("-")) && opts.dry_run => {
+            echo!("Would read toolchain archive from stdin");
+            None
+        }
+        None if opts.toolchain_path == Some(Path::new("-")) => {
+            let rv = read_toolchain_from_stdin(app_dir, opts.toolchain_sha256);
+            log.result("read toolchain", &rv, "stdin");
+            downloaded_toolchain = rv?;
+            toolchain_sha256 = None;
+            Some(downloaded_toolchain.as_path())
+        }
+        None => opts.toolchain_path,
+    };
+
+    // Register a toolchain if provided.
+    if let Some(toolchain_path) = toolchain_path {
+        if opts.dry_run {
+            echo!(
+                "Would register toolchain at {}",
+                style(toolchain_path.display()).cyan()
+            );
+        } else {
+            echo!(
+                "Registering toolchain at {}",
+                style(toolchain_path.display()).cyan()
+            );
+            if !opts.allow_arch_mismatch {
+                check_toolchain_arch(toolchain_path, opts.toolchain_arch)?;
+            }
+            let rv = register_toolchain(
+                toolchain_path,
+                None,
+                opts.toolchain_arch,
+                toolchain_sha256,
+                |ver| {
+                    if ver.name != "cpython" {
+                        bail!("Only cpython toolchains are allowed, got '{}'", ver.name);
+                    } else if !is_self_compatible_toolchain(ver) {
+                        bail!(
+                            "Toolchain {} is not version compatible for internal use.",
+                            ver
+                        );
+                    }
+                    Ok(())
+                },
+            );
+            log.result(
+                "register toolchain",
+                &rv,
+                &toolchain_path.display().to_string(),
+            );
+            let version = rv?;
+            echo!("Registered toolchain as {}", style(&version).cyan());
+            manifest.toolchain = Some(version.to_string());
+        }
+    } else if matches!(mode, InstallMode::Default)
+        && !opts.dry_run
+        && opts.toolchain_url.is_none()
+        && opts.prompt_timeout.is_none()
+        && console::Term::stderr().is_term()
     {
-        if !env::split_paths(&env::var_os("PATH").unwrap())
-            .any(|x| same_file::is_same_file(x, &shims).unwrap_or(false))
+        // let the user pick the python for the internals, the picked one is
+        // remembered in the config so that later bootstraps use it too.  The
+        // picker cannot time out, so it's not shown with a prompt timeout.
+        if let Some(version) = pick_self_toolchain()? {
+            let rv = fetch(&version.into(), opts.output);
+            log.result("register toolchain", &rv, "picked");
+            let version = rv?;
+            save_self_python(&version)?;
+            echo!("Using {} for rye internals", style(&version).cyan());
+            manifest.toolchain = Some(version.to_string());
+        }
+    }
+
+    // Ensure internals next.  In offline mode or without a self venv we only
+    // set up the shims and defer the bootstrapping until the internals are
+    // first needed.
+    if opts.dry_run {
+        if matches!(mode, InstallMode::Offline) || opts.no_self_venv {
+            echo!("Would update shims in {}", style(shims.display()).cyan());
+        } else {
+            echo!(
+                "Would bootstrap rye internals in {}",
+                style(app_dir.join("self").display()).cyan()
+            );
+        }
+    } else if matches!(mode, InstallMode::Offline) {
+        update_core_shims(&shims, &target)?;
+        echo!("Skipped bootstrapping rye internals (offline mode)");
+    } else if opts.no_self_venv {
+        update_core_shims(&shims, &target)?;
+        echo!("Skipped creating the self venv, it will be created on demand");
+    } else {
+        let rv = ensure_self_venv(opts.output);
+        log.result(
+            "ensure self venv",
+            &rv,
+            &app_dir.join("self").display().to_string(),
+        );
+        let self_path = rv?;
+        // bootstrapping only knows about the default name of the executable
+        update_core_shims(&shims, &target)?;
+        echo!(
+            "Updated self-python installation at {}",
+            style(self_path.display()).cyan()
+        );
+        manifest.self_venv = Some(self_path);
+    }
+
+    if !opts.dry_run {
+        save_telemetry_default(opts.no_telemetry)?;
+        write_shims_version(&shims, env!("CARGO_PKG_VERSION"))?;
+        #[cfg(unix)]
         {
+            if opts.system {
+                make_readable_for_all(app_dir)?;
+            }
+        }
+        // only what the installer placed, not the shims of installed tools
+        manifest.shims = get_core_shim_paths(&shims);
+        manifest.save(app_dir)?;
+    }
+
+    // show what sourcing the env file does to help with debugging the PATH setup
+    if opts.output == CommandOutput::Verbose {
+        if let Some(ref env_file) = manifest.env_file {
+            if let Ok(contents) = fs::read_to_string(env_file) {
+                echo!();
+                echo!(
+                    "The env file {} contains:",
+                    style(env_file.display()).cyan()
+                );
+                echo!();
+                for line in contents.lines() {
+                    echo!("    {}", line);
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        if !opts.no_modify_path && !is_on_path(&shims) {
             echo!();
             echo!(
                 "The rye directory {} was not detected on {}.",
@@ -447,29 +3792,77 @@ fn perform_install(mode: InstallMode, toolchain_path: Option<&Path>) -> Result<(
                 style("PATH").cyan()
             );
             echo!("It is highly recommended that you add it.");
-            echo!("Add this at the end of your .profile, .zprofile or similar:");
-            echo!();
-            echo!("    source \"{}/env\"", rye_home);
-            echo!();
-            if is_fish() {
-                echo!("To make it work with fish, run this once instead:");
-                echo!();
-                echo!("    set -Ua fish_user_paths \"{}/shims\"", rye_home);
+            if opts.system {
+                echo!("To add it for all users, create /etc/profile.d/rye.sh with:");
                 echo!();
+                echo!("    . \"{}/env\"", rye_home);
+            } else {
+                match detect_shell_setup() {
+                    ShellSetup::Fish => {
+                        echo!("To make it work with fish, run this once:");
+                        echo!();
+                        echo!("    set -Ua fish_user_paths \"{}/shims\"", rye_home);
+                    }
+                    ShellSetup::Source { rc_file, command } => {
+                        echo!("Add this at the end of your ~/{}:", rc_file);
+                        echo!();
+                        echo!("    {} \"{}/env\"", command, rye_home);
+                    }
+                }
             }
+            echo!();
             echo!("Note: after adding rye to your path, restart your shell for it to take effect.");
         }
     }
     #[cfg(windows)]
     {
+        if !opts.no_modify_path && !is_on_path(&shims) && !is_on_user_path(&shims)? {
+            echo!();
+            if opts.dry_run {
+                echo!("Would add {} to PATH", style(shims.display()).cyan());
+            } else if !matches!(mode, InstallMode::Default)
+                || confirm(
+                    &format!("Add {} to your PATH?", shims.display()),
+                    false,
+                    opts.prompt_timeout,
+                )?
+            {
+                add_to_user_path(&shims)?;
+                echo!("Added {} to PATH", style(shims.display()).cyan());
+                echo!("Note: restart your shell for it to take effect.");
+            } else {
+                print_windows_path_advice(&shims);
+            }
+        }
+    }
+
+    // make sure the installed executable actually runs and that it's the one
+    // that is found on the PATH.
+    if !opts.dry_run && opts.output != CommandOutput::Quiet {
+        verify_installed_exe(&target);
+    }
+
+    if shim_name != "rye" {
         echo!();
-        echo!("Note: You need to manually add {DEFAULT_HOME} to your PATH.");
+        echo!(
+            "Rye was installed as {}, use that name to invoke it.",
+            style(shim_name).cyan()
+        );
     }
 
     echo!("For more information read https://mitsuhiko.github.io/rye/guide/installation");
 
     echo!();
-    echo!("{}", style("All done!").green());
+    drop(echo_guard);
+    if opts.dry_run {
+        echo!(
+            "{}",
+            style("Dry run complete, nothing was changed.").green()
+        );
+    } else {
+        echo!("{}", style("All done!").green());
+    }
+    log.record("done", "ok", None);
 
     Ok(())
 }
@@ -485,10 +3878,7 @@ pub fn auto_self_install() -> Result<bool, Error> {
     let toolchain_path = env::var_os("RYE_TOOLCHAIN");
 
     let app_dir = get_app_dir();
-    let rye_exe = app_dir
-        .join("shims")
-        .join("rye")
-        .with_extension(EXE_EXTENSION);
+    let rye_exe = get_installed_rye_exe(app_dir);
 
     // it's already installed, don't install
     if app_dir.is_dir() && rye_exe.is_file() {
@@ -503,8 +3893,153 @@ pub fn auto_self_install() -> Result<bool, Error> {
 
         perform_install(
             InstallMode::AutoInstall,
-            toolchain_path.as_ref().map(Path::new),
+            &InstallOptions {
+                toolchain_path: toolchain_path.as_ref().map(Path::new),
+                ..Default::default()
+            },
         )?;
         Ok(true)
     }
 }
+
+#[cfg(unix)]
+#[test]
+fn test_is_env_sourcing_line() {
+    let env_files = ["$HOME/.rye/env".to_string()];
+    assert!(is_env_sourcing_line(
+        "source \"$HOME/.rye/env\"\n",
+        &env_files
+    ));
+    assert!(is_env_sourcing_line("  . \"$HOME/.rye/env\"", &env_files));
+    assert!(is_env_sourcing_line("source $HOME/.rye/env", &env_files));
+    assert!(!is_env_sourcing_line(
+        "source \"$HOME/.other/env\"",
+        &env_files
+    ));
+    assert!(!is_env_sourcing_line(
+        "# source \"$HOME/.rye/env\"",
+        &env_files
+    ));
+}
+
+#[test]
+fn test_render_env_file() {
+    let rv = render_env_file(None, true, "/opt/rye").unwrap();
+    assert!(rv.contains("export RYE_HOME=\"/opt/rye\""));
+    assert!(rv.contains("export PATH=\"/opt/rye/shims:$PATH\""));
+
+    let template = tempfile::NamedTempFile::new().unwrap();
+    fs::write(template.path(), "export RYE_HOME=\"{{ rye_home }}\"\n").unwrap();
+    assert!(render_env_file(Some(template.path()), true, "/opt/rye").is_err());
+}
+
+#[test]
+fn test_write_decompressed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rye");
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"gzip release").unwrap();
+    let compressed = encoder.finish().unwrap();
+    write_decompressed(
+        &compressed[..],
+        compressed.len() as u64,
+        &path,
+        CommandOutput::Quiet,
+    )
+    .unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"gzip release");
+
+    let compressed = zstd::encode_all(&b"zstd release"[..], 0).unwrap();
+    write_decompressed(
+        &compressed[..],
+        compressed.len() as u64,
+        &path,
+        CommandOutput::Quiet,
+    )
+    .unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"zstd release");
+}
+
+#[test]
+fn test_unhide_subcommands() {
+    let cmd = unhide_subcommands(super::Args::command());
+    let install = cmd
+        .find_subcommand("self")
+        .and_then(|x| x.find_subcommand("install"))
+        .unwrap();
+    assert!(!install.is_hide_set());
+}
+
+#[test]
+fn test_check_release_checksum() {
+    assert!(check_release_checksum(
+        b"hello",
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        "test"
+    )
+    .is_ok());
+    let err = check_release_checksum(b"hello", "sha256:00", "test").unwrap_err();
+    match err.downcast_ref::<SelfUpdateError>() {
+        Some(SelfUpdateError::ChecksumMismatch {
+            expected, saved_to, ..
+        }) => {
+            assert_eq!(expected, "00");
+            let saved_to = saved_to.as_ref().unwrap();
+            assert_eq!(fs::read(saved_to).unwrap(), b"hello");
+            fs::remove_file(saved_to).ok();
+        }
+        _ => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_write_lazy_completion() {
+    let mut out = Vec::new();
+    write_lazy_completion(ShellType::Bash, false, "rye", &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("    eval \"$(rye self completion --shell bash)\"\n"));
+    assert!(out.ends_with("complete -F _rye_lazy -o bashdefault -o default rye\n"));
+    assert!(write_lazy_completion(ShellType::Elvish, false, "rye", &mut Vec::new()).is_err());
+
+    let mut out = Vec::new();
+    write_lazy_completion(ShellType::Zsh, false, "rye2", &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("eval \"$(rye2 self completion --shell zsh --bin-name rye2)\"\n"));
+    assert!(out.ends_with("compdef _rye2_lazy rye2\n"));
+}
+
+#[test]
+fn test_get_self_backups() {
+    let app_dir = tempfile::tempdir().unwrap();
+    let backup = backup_current_exe(app_dir.path(), &env::current_exe().unwrap()).unwrap();
+    fs::create_dir_all(app_dir.path().join("self-backups").join("empty")).unwrap();
+    let backups = get_self_backups(app_dir.path());
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(backups[0].exe, backup);
+    assert!(backups[0].size > 0);
+}
+
+#[test]
+fn test_render_shell_env() {
+    let app_dir = Path::new("/opt/rye");
+    let bash = render_shell_env(ShellType::Bash, app_dir).unwrap();
+    assert!(bash.contains("export RYE_HOME=\"/opt/rye\"\n"));
+    assert!(bash.contains("export PATH=\"/opt/rye/shims:$PATH\"\n"));
+    let fish = render_shell_env(ShellType::Fish, app_dir).unwrap();
+    assert!(fish.starts_with("set -gx RYE_HOME \"/opt/rye\"\n"));
+    assert!(render_shell_env(ShellType::Nushell, app_dir).is_err());
+}
+
+#[test]
+fn test_read_answer() {
+    let (tx, rx) = mpsc::channel();
+    tx.send(Ok("y".to_string())).unwrap();
+    tx.send(Ok("maybe".to_string())).unwrap();
+    tx.send(Ok("n".to_string())).unwrap();
+    let timeout = Duration::from_secs(5);
+    assert!(read_answer("first?", false, timeout, &rx).unwrap());
+    assert!(!read_answer("second?", false, timeout, &rx).unwrap());
+    assert!(!read_answer("third?", true, Duration::ZERO, &rx).unwrap());
+}
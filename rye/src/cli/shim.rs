@@ -1,6 +1,8 @@
 use std::convert::Infallible;
 use std::env;
+use std::env::consts::EXE_EXTENSION;
 use std::ffi::{OsStr, OsString};
+use std::path::Path;
 use std::str::FromStr;
 
 use anyhow::{anyhow, bail, Context, Error};
@@ -9,9 +11,12 @@ use std::process::Command;
 use which::which_in_global;
 
 use crate::bootstrap::{ensure_self_venv, get_pip_runner};
+use crate::cli::rye::get_installed_rye_exe;
 use crate::config::Config;
 use crate::consts::VENV_BIN;
-use crate::platform::{get_python_version_request_from_pyenv_pin, get_toolchain_python_bin};
+use crate::platform::{
+    get_app_dir, get_python_version_request_from_pyenv_pin, get_toolchain_python_bin,
+};
 use crate::pyproject::{latest_available_python_version, PyProject};
 use crate::sources::{PythonVersion, PythonVersionRequest};
 use crate::sync::{sync, SyncOptions};
@@ -26,16 +31,26 @@ fn detect_shim(args: &[OsString]) -> Option<String> {
         return None;
     }
 
+    // the wrappers of portable installations tell us the shim name.  It's
+    // removed so that rye invocations from the shimmed process are not affected.
+    if let Some(shim_name) = env::var_os("RYE_SHIM") {
+        env::remove_var("RYE_SHIM");
+        return shim_name.into_string().ok();
+    }
+
     let path = env::current_exe().ok()?;
     let shim_name = path.file_name()?;
 
-    // rye is itself placed in the shims folder, so it must not
-    // detect itself.
-    if shim_name == "rye" || shim_name == "rye.exe" {
+    if path.parent()?.file_name() != Some(OsStr::new("shims")) {
         return None;
     }
 
-    if path.parent()?.file_name() != Some(OsStr::new("shims")) {
+    // rye is itself placed in the shims folder, so it must not
+    // detect itself.  It can be installed under another name
+    // (eg: rye2) which is recorded in the install manifest.
+    if Path::new(shim_name) == Path::new("rye").with_extension(EXE_EXTENSION)
+        || Some(shim_name) == get_installed_rye_exe(get_app_dir()).file_name()
+    {
         return None;
     }
 
@@ -75,8 +90,16 @@ fn get_pip_shim(
 /// placed in the virtualenv.
 fn find_shadowed_target(target: &str, args: &[OsString]) -> Result<Option<Vec<OsString>>, Error> {
     let exe = env::current_exe()?;
+    let shims = exe.parent();
     for bin in which::which_all(target)? {
-        if is_same_file(&bin, &exe).unwrap_or(false) {
+        // skip ourselves and other shims such as the wrappers of portable
+        // installations
+        if is_same_file(&bin, &exe).unwrap_or(false)
+            || bin
+                .parent()
+                .zip(shims)
+                .map_or(false, |(a, b)| is_same_file(a, b).unwrap_or(false))
+        {
             continue;
         }
         let mut args = args.to_vec();
@@ -2,6 +2,7 @@ use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::env::consts::{ARCH, OS};
 use std::fs;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -12,9 +13,10 @@ use console::style;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::bootstrap::is_self_compatible_toolchain;
 use crate::platform::{get_canonical_py_path, list_known_toolchains};
 use crate::sources::{iter_downloadable, PythonVersion};
-use crate::utils::symlink_file;
+use crate::utils::{check_checksum, symlink_file, QuietExit};
 
 const INSPECT_SCRIPT: &str = r#"
 import json
@@ -53,6 +55,21 @@ pub struct RegisterCommand {
     /// Name of the toolchain.  If not provided a name is auto detected.
     #[arg(short, long)]
     name: Option<String>,
+    /// Architecture of the toolchain.  If not provided it's assumed to be native.
+    ///
+    /// On macOS the architecture is validated against the binary.
+    #[arg(long)]
+    arch: Option<String>,
+}
+
+/// Checks if a Python binary can be used for rye's internals.
+///
+/// This runs the same validation as the installer does for the toolchain
+/// passed with `--toolchain`.
+#[derive(Parser, Debug)]
+pub struct CheckCommand {
+    /// Path to the Python binary.
+    path: PathBuf,
 }
 
 /// Removes a toolchain.
@@ -76,12 +93,13 @@ pub struct ListCommand {
 #[derive(ValueEnum, Copy, Clone, Serialize, Debug, PartialEq)]
 #[value(rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
-enum Format {
+pub enum Format {
     Json,
 }
 
 #[derive(Parser, Debug)]
 enum SubCommand {
+    Check(CheckCommand),
     Fetch(crate::cli::fetch::Args),
     List(ListCommand),
     Register(RegisterCommand),
@@ -91,6 +109,7 @@ enum SubCommand {
 pub fn execute(cmd: Args) -> Result<(), Error> {
     match cmd.command {
         SubCommand::Register(args) => register(args),
+        SubCommand::Check(args) => check(args),
         SubCommand::Fetch(args) => crate::cli::fetch::execute(args),
         SubCommand::List(args) => list(args),
         SubCommand::Remove(args) => remove(args),
@@ -98,11 +117,48 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
 }
 
 fn register(cmd: RegisterCommand) -> Result<(), Error> {
-    let target_version = register_toolchain(&cmd.path, cmd.name.as_deref(), |_| Ok(()))?;
+    let target_version = register_toolchain(
+        &cmd.path,
+        cmd.name.as_deref(),
+        cmd.arch.as_deref(),
+        None,
+        |_| Ok(()),
+    )?;
     echo!("Registered {} as {}", cmd.path.display(), target_version);
     Ok(())
 }
 
+fn check(cmd: CheckCommand) -> Result<(), Error> {
+    let info = inspect_interpreter(&cmd.path)?;
+    let version: PythonVersion = format!(
+        "{}@{}",
+        info.python_implementation.to_ascii_lowercase(),
+        info.python_version
+    )
+    .parse()?;
+    echo!("{} is {}", cmd.path.display(), style(&version).cyan());
+
+    if version.name != "cpython" {
+        echo!(
+            "{} only cpython toolchains can be used, got '{}'",
+            style("unusable:").red(),
+            version.name
+        );
+    } else if !is_self_compatible_toolchain(&version) {
+        echo!(
+            "{} the version is not compatible for internal use",
+            style("unusable:").red(),
+        );
+    } else {
+        echo!(
+            "{} the toolchain can be used for rye's internals",
+            style("usable:").green()
+        );
+        return Ok(());
+    }
+    Err(QuietExit(1).into())
+}
+
 pub fn remove(cmd: RemoveCommand) -> Result<(), Error> {
     let ver: PythonVersion = cmd.version.parse()?;
     let path = get_canonical_py_path(&ver)?;
@@ -188,25 +244,26 @@ fn list(cmd: ListCommand) -> Result<(), Error> {
 pub fn register_toolchain<F>(
     path: &Path,
     name: Option<&str>,
+    arch: Option<&str>,
+    sha256: Option<&str>,
     validate: F,
 ) -> Result<PythonVersion, Error>
 where
     F: FnOnce(&PythonVersion) -> Result<(), Error>,
 {
-    let output = Command::new(path)
-        .arg("-c")
-        .arg(INSPECT_SCRIPT)
-        .output()
-        .context("error executing interpreter to inspect version")?;
-    if !output.status.success() {
-        bail!("passed path does not appear to be a valid Python installation");
+    // verify the interpreter before it's executed for the first time
+    if let Some(sha256) = sha256 {
+        let contents =
+            fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        check_checksum(&contents, sha256)
+            .with_context(|| format!("hash check of {} failed", path.display()))?;
     }
 
-    let info: InspectInfo = serde_json::from_slice(&output.stdout)
-        .context("could not parse interpreter output as json")?;
-    let target_version = match name {
-        Some(ref name) => format!("{}@{}", name, info.python_version),
-        None => {
+    let info = inspect_interpreter(path)?;
+    let target_version = match (name, arch) {
+        (Some(name), None) => format!("{}@{}", name, info.python_version),
+        (Some(name), Some(arch)) => format!("{}-{}@{}", name, arch, info.python_version),
+        (None, None) => {
             format!(
                 "{}{}@{}",
                 info.python_implementation.to_ascii_lowercase(),
@@ -214,7 +271,30 @@ where
                 info.python_version
             )
         }
+        (None, Some(arch)) => {
+            if info.python_debug {
+                bail!("debug interpreters cannot be registered with an explicit architecture");
+            }
+            format!(
+                "{}-{}@{}",
+                info.python_implementation.to_ascii_lowercase(),
+                arch,
+                info.python_version
+            )
+        }
     };
+    if let Some(arch) = arch {
+        if let Some(binary_archs) = get_binary_archs(path) {
+            if !binary_archs.iter().any(|x| *x == arch) {
+                bail!(
+                    "interpreter at {} is built for {} and not {}",
+                    path.display(),
+                    binary_archs.join(", "),
+                    arch
+                );
+            }
+        }
+    }
     let target_version: PythonVersion = target_version.parse()?;
     validate(&target_version)
         .with_context(|| anyhow!("{} is not a valid toolchain", &target_version))?;
@@ -255,3 +335,116 @@ where
 
     Ok(target_version)
 }
+
+/// Runs the interpreter to figure out what it is.
+fn inspect_interpreter(path: &Path) -> Result<InspectInfo, Error> {
+    let output = Command::new(path)
+        .arg("-c")
+        .arg(INSPECT_SCRIPT)
+        .output()
+        .context("error executing interpreter to inspect version")?;
+    if !output.status.success() {
+        bail!("passed path does not appear to be a valid Python installation");
+    }
+
+    serde_json::from_slice(&output.stdout).context("could not parse interpreter output as json")
+}
+
+/// Returns the architectures an executable is built for.
+///
+/// This understands Mach-O, ELF and PE binaries and returns `None` for
+/// anything else such as scripts.
+pub fn get_binary_archs(path: &Path) -> Option<Vec<&'static str>> {
+    get_macho_archs(path).or_else(|| get_elf_or_pe_arch(path).map(|x| vec![x]))
+}
+
+/// Returns the architecture of an ELF or PE binary.
+fn get_elf_or_pe_arch(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 64];
+    let mut file = fs::File::open(path).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if header.starts_with(b"\x7fELF") {
+        // the machine field follows the byte order of the binary
+        let machine = header[18..20].try_into().unwrap();
+        let machine = if header[5] == 2 {
+            u16::from_be_bytes(machine)
+        } else {
+            u16::from_le_bytes(machine)
+        };
+        Some(match machine {
+            3 => "x86",
+            62 => "x86_64",
+            183 => "aarch64",
+            _ => "unknown",
+        })
+    } else if header.starts_with(b"MZ") {
+        // the DOS header points to the PE header which starts with the machine
+        let offset = u32::from_le_bytes(header[0x3c..0x40].try_into().unwrap());
+        file.seek(SeekFrom::Start(offset as u64)).ok()?;
+        let mut pe_header = [0u8; 6];
+        file.read_exact(&mut pe_header).ok()?;
+        if &pe_header[..4] != b"PE\0\0" {
+            return None;
+        }
+        Some(
+            match u16::from_le_bytes(pe_header[4..6].try_into().unwrap()) {
+                0x014c => "x86",
+                0x8664 => "x86_64",
+                0xaa64 => "aarch64",
+                _ => "unknown",
+            },
+        )
+    } else {
+        None
+    }
+}
+
+/// Returns the architectures contained in a Mach-O binary.
+///
+/// Returns `None` if the file is not a Mach-O binary.  Universal binaries
+/// report all contained architectures.
+fn get_macho_archs(path: &Path) -> Option<Vec<&'static str>> {
+    fn arch_name(cpu_type: u32) -> &'static str {
+        match cpu_type {
+            7 => "x86",
+            0x0100_0007 => "x86_64",
+            0x0100_000c => "aarch64",
+            _ => "unknown",
+        }
+    }
+
+    let mut header = [0u8; 8];
+    let mut file = fs::File::open(path).ok()?;
+    file.read_exact(&mut header).ok()?;
+    let magic = u32::from_be_bytes(header[..4].try_into().unwrap());
+    match magic {
+        // thin binaries in little endian (32 and 64 bit)
+        0xcefa_edfe | 0xcffa_edfe => Some(vec![arch_name(u32::from_le_bytes(
+            header[4..8].try_into().unwrap(),
+        ))]),
+        // universal binaries contain a big endian table of architectures
+        0xcafe_babe => {
+            let count = u32::from_be_bytes(header[4..8].try_into().unwrap());
+            let mut rv = Vec::new();
+            for _ in 0..count.min(16) {
+                let mut entry = [0u8; 20];
+                file.read_exact(&mut entry).ok()?;
+                rv.push(arch_name(u32::from_be_bytes(
+                    entry[..4].try_into().unwrap(),
+                )));
+            }
+            Some(rv)
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_get_binary_archs() {
+    let exe = std::env::current_exe().unwrap();
+    assert!(get_binary_archs(&exe).unwrap().contains(&ARCH));
+
+    let script = tempfile::NamedTempFile::new().unwrap();
+    fs::write(script.path(), "#!/bin/sh\nexec python3 \"$@\"\n").unwrap();
+    assert_eq!(get_binary_archs(script.path()), None);
+}
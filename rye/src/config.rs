@@ -108,6 +108,25 @@ impl Config {
         .context("failed to get default toolchain")
     }
 
+    /// Returns the toolchain that should be used for rye's internals.
+    ///
+    /// This can be overridden with the `RYE_SELF_PYTHON` environment variable.
+    pub fn self_python(&self) -> Result<Option<PythonVersionRequest>, Error> {
+        match std::env::var("RYE_SELF_PYTHON").ok().or_else(|| {
+            self.doc
+                .get("behavior")
+                .and_then(|x| x.get("self-python"))
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string())
+        }) {
+            Some(ver) => ver
+                .parse()
+                .map(Some)
+                .with_context(|| format!("invalid self python version '{}'", ver)),
+            None => Ok(None),
+        }
+    }
+
     /// Returns the default build system
     pub fn default_build_system(&self) -> Option<BuildSystem> {
         match self
@@ -186,17 +205,55 @@ impl Config {
             .unwrap_or(false)
     }
 
-    /// Returns the HTTP proxy that should be used.
-    pub fn http_proxy_url(&self) -> Option<String> {
-        std::env::var("http_proxy").ok().or_else(|| {
+    /// Returns the version of pip-tools that is used for locking and syncing.
+    ///
+    /// This can be overridden with the `RYE_PIP_TOOLS_VERSION` environment variable.
+    pub fn pip_tools_version(&self) -> Option<String> {
+        std::env::var("RYE_PIP_TOOLS_VERSION").ok().or_else(|| {
             self.doc
-                .get("proxy")
-                .and_then(|x| x.get("http"))
+                .get("behavior")
+                .and_then(|x| x.get("pip-tools-version"))
                 .and_then(|x| x.as_str())
                 .map(|x| x.to_string())
         })
     }
 
+    /// Returns `true` if usage data may be sent.
+    ///
+    /// Rye does not send any usage data today, but every network feature that
+    /// is not required for a command has to check this.  It's off unless
+    /// explicitly enabled.
+    pub fn telemetry_enabled(&self) -> bool {
+        self.doc
+            .get("behavior")
+            .and_then(|x| x.get("telemetry"))
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Returns the release channel that updates are fetched from.
+    pub fn update_channel(&self) -> Option<String> {
+        self.doc
+            .get("behavior")
+            .and_then(|x| x.get("update-channel"))
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string())
+    }
+
+    /// Returns the HTTP proxy that should be used.
+    pub fn http_proxy_url(&self) -> Option<String> {
+        std::env::var("HTTP_PROXY")
+            .ok()
+            .or_else(|| std::env::var("http_proxy").ok())
+            .or_else(|| {
+                self.doc
+                    .get("proxy")
+                    .and_then(|x| x.get("http"))
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.to_string())
+            })
+    }
+
     /// Returns the HTTPS proxy that should be used.
     pub fn https_proxy_url(&self) -> Option<String> {
         std::env::var("HTTPS_PROXY")
@@ -211,6 +268,20 @@ impl Config {
             })
     }
 
+    /// Returns the hosts that should not go through the proxy.
+    pub fn no_proxy(&self) -> Option<String> {
+        std::env::var("NO_PROXY")
+            .ok()
+            .or_else(|| std::env::var("no_proxy").ok())
+            .or_else(|| {
+                self.doc
+                    .get("proxy")
+                    .and_then(|x| x.get("no-proxy"))
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.to_string())
+            })
+    }
+
     /// Returns the list of default sources.
     pub fn sources(&self) -> Result<Vec<SourceRef>, Error> {
         let mut rv = Vec::new();
@@ -3,3 +3,13 @@ pub const VENV_BIN: &str = "bin";
 
 #[cfg(windows)]
 pub const VENV_BIN: &str = "Scripts";
+
+/// Marks a shims folder of a portable installation.
+pub const PORTABLE_MARKER: &str = ".portable";
+
+/// Records the version of rye the core shims point to.
+pub const SHIMS_VERSION_STAMP: &str = ".version";
+
+/// The default home of a system-wide installation.
+#[cfg(unix)]
+pub const SYSTEM_APP_DIR: &str = "/opt/rye";
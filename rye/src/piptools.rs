@@ -4,17 +4,24 @@ use std::process::Command;
 use anyhow::{bail, Context, Error};
 
 use crate::bootstrap::ensure_self_venv;
+use crate::config::Config;
 use crate::consts::VENV_BIN;
 use crate::platform::get_app_dir;
 use crate::sources::PythonVersion;
 use crate::sync::create_virtualenv;
 use crate::utils::{get_venv_python_bin, CommandOutput};
 
-const PIP_TOOLS_VERSION: &str = "pip-tools==6.13.0";
+const PIP_TOOLS_VERSION: &str = "6.13.0";
 
 fn get_pip_tools_bin(py_ver: &PythonVersion, output: CommandOutput) -> Result<PathBuf, Error> {
     let self_venv = ensure_self_venv(output)?;
-    let key = format!("{}@{}.{}", py_ver.name, py_ver.major, py_ver.minor);
+    let pinned_version = Config::current().pip_tools_version();
+    let mut key = format!("{}@{}.{}", py_ver.name, py_ver.major, py_ver.minor);
+    // a pinned version gets its own virtualenv so that changing the pin
+    // does not pick up a venv with another version.
+    if let Some(ref version) = pinned_version {
+        key.push_str(&format!("+{}", version));
+    }
     let venv = get_app_dir().join("pip-tools").join(key);
 
     let py = get_venv_python_bin(&venv);
@@ -32,7 +39,10 @@ fn get_pip_tools_bin(py_ver: &PythonVersion, output: CommandOutput) -> Result<Pa
     cmd.arg("--python")
         .arg(&py)
         .arg("install")
-        .arg(PIP_TOOLS_VERSION)
+        .arg(format!(
+            "pip-tools=={}",
+            pinned_version.as_deref().unwrap_or(PIP_TOOLS_VERSION)
+        ))
         .arg("-q")
         .env("PIP_DISABLE_PIP_VERSION_CHECK", "1");
     if output == CommandOutput::Verbose {
@@ -6,6 +6,7 @@ use std::{env, fs};
 use anyhow::{anyhow, Context, Error};
 
 use crate::config::Config;
+use crate::consts::PORTABLE_MARKER;
 use crate::pyproject::latest_available_python_version;
 use crate::sources::{PythonVersion, PythonVersionRequest};
 
@@ -13,7 +14,18 @@ static APP_DIR: Mutex<Option<&'static PathBuf>> = Mutex::new(None);
 
 pub fn init() -> Result<(), Error> {
     let home = if let Some(rye_home) = env::var_os("RYE_HOME") {
-        PathBuf::from(rye_home)
+        #[cfg(windows)]
+        {
+            PathBuf::from(expand_env_vars(&rye_home.to_string_lossy()))
+        }
+        #[cfg(unix)]
+        {
+            PathBuf::from(rye_home)
+        }
+    } else if let Some(portable_home) = get_portable_home() {
+        portable_home
+    } else if let Some(xdg_home) = get_xdg_home() {
+        xdg_home
     } else {
         simple_home_dir::home_dir()
             .map(|x| x.join(".rye"))
@@ -23,6 +35,67 @@ pub fn init() -> Result<(), Error> {
     Ok(())
 }
 
+/// Expands `%VAR%` references to environment variables.
+///
+/// References to unset variables are left alone, like `cmd.exe` does.
+#[cfg(windows)]
+fn expand_env_vars(s: &str) -> String {
+    let mut rv = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('%') {
+        rv.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => match env::var(&after[..end]) {
+                Ok(value) if end > 0 => {
+                    rv.push_str(&value);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    rv.push('%');
+                    rest = after;
+                }
+            },
+            None => {
+                rv.push('%');
+                rest = after;
+            }
+        }
+    }
+    rv.push_str(rest);
+    rv
+}
+
+/// Returns the home folder of a portable installation rye is running from.
+///
+/// A portable installation is recognized by a marker file in the shims folder
+/// next to the executable.
+fn get_portable_home() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    let shims = exe.parent()?;
+    if shims.join(PORTABLE_MARKER).is_file() {
+        shims.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+/// Returns the home folder in the XDG data directory if enabled.
+///
+/// This is opted into by setting `RYE_USE_XDG` to `1` and only supported
+/// on Linux.
+pub fn get_xdg_home() -> Option<PathBuf> {
+    if !cfg!(target_os = "linux") || env::var("RYE_USE_XDG").ok().as_deref() != Some("1") {
+        return None;
+    }
+    // relative paths are invalid according to the spec and ignored
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+        .or_else(|| simple_home_dir::home_dir().map(|x| x.join(".local").join("share")))?;
+    Some(data_home.join("rye"))
+}
+
 /// Returns the application directory.
 pub fn get_app_dir() -> &'static Path {
     APP_DIR.lock().unwrap().expect("platform not initialized")
@@ -270,3 +343,18 @@ pub fn write_credentials(doc: &toml_edit::Document) -> Result<(), Error> {
 pub fn get_credentials_filepath() -> Result<PathBuf, Error> {
     Ok(get_app_dir().join("credentials"))
 }
+
+#[cfg(windows)]
+#[test]
+fn test_expand_env_vars() {
+    env::set_var("RYE_TEST_EXPAND", "C:\\Users\\rye");
+    assert_eq!(
+        expand_env_vars("%RYE_TEST_EXPAND%\\.rye"),
+        "C:\\Users\\rye\\.rye"
+    );
+    assert_eq!(
+        expand_env_vars("%RYE_TEST_UNSET%\\.rye"),
+        "%RYE_TEST_UNSET%\\.rye"
+    );
+    assert_eq!(expand_env_vars("100%"), "100%");
+}
@@ -1,3 +1,4 @@
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fs};
@@ -14,7 +15,7 @@ use crate::lock::{
     LockMode, LockOptions,
 };
 use crate::piptools::get_pip_sync;
-use crate::platform::get_toolchain_python_bin;
+use crate::platform::{get_app_dir, get_toolchain_python_bin};
 use crate::pyproject::{get_current_venv_python_version, ExpandedSources, PyProject};
 use crate::sources::PythonVersion;
 use crate::utils::{get_venv_python_bin, set_proxy_variables, symlink_dir, CommandOutput};
@@ -153,6 +154,8 @@ pub fn sync(cmd: SyncOptions) -> Result<(), Error> {
             })?,
         )
         .context("failed writing venv marker file")?;
+        // failing to record the virtualenv only affects the uninstall warning
+        register_venv(&venv).ok();
     }
 
     // prepare necessary utilities for pip-sync.  This is a super crude
@@ -284,6 +287,30 @@ pub fn sync(cmd: SyncOptions) -> Result<(), Error> {
     Ok(())
 }
 
+/// Records a virtualenv created by rye in `venvs.txt` in the rye home.
+fn register_venv(venv: &Path) -> Result<(), Error> {
+    let venvs = get_registered_venvs();
+    if venvs.iter().any(|x| x == venv) {
+        return Ok(());
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_app_dir().join("venvs.txt"))?;
+    writeln!(file, "{}", venv.display())?;
+    Ok(())
+}
+
+/// Returns the virtualenvs created by rye that still exist.
+pub fn get_registered_venvs() -> Vec<PathBuf> {
+    fs::read_to_string(get_app_dir().join("venvs.txt"))
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .filter(|x| x.join("rye-venv.json").is_file())
+        .collect()
+}
+
 pub fn create_virtualenv(
     output: CommandOutput,
     self_venv: &Path,
@@ -2,9 +2,13 @@ use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static ECHO_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static ECHO_SUPPRESSED: AtomicBool = AtomicBool::new(false);
 
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
+    if ECHO_SUPPRESSED.load(Ordering::Relaxed) {
+        return;
+    }
     // use eprintln and println so that tests can still intercept this
     if ECHO_TO_STDERR.load(Ordering::Relaxed) {
         eprintln!("{}", args);
@@ -29,6 +33,22 @@ impl Drop for RedirectGuard {
     }
 }
 
+/// Until the guard is dropped, echo output is discarded.
+pub fn suppress_echo(yes: bool) -> SuppressGuard {
+    let old = ECHO_SUPPRESSED.load(Ordering::Relaxed);
+    ECHO_SUPPRESSED.store(yes, Ordering::Relaxed);
+    SuppressGuard(old)
+}
+
+#[must_use]
+pub struct SuppressGuard(bool);
+
+impl Drop for SuppressGuard {
+    fn drop(&mut self) {
+        ECHO_SUPPRESSED.store(self.0, Ordering::Relaxed);
+    }
+}
+
 /// Echo a line to the output stream (usually stdout).
 macro_rules! echo {
     () => {
@@ -1,15 +1,17 @@
 use std::borrow::Cow;
 use std::convert::Infallible;
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 use std::{fmt, fs};
 
 use anyhow::{anyhow, bail, Error};
 use once_cell::sync::Lazy;
 use pep508_rs::{Requirement, VersionOrUrl};
 use regex::{Captures, Regex};
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha512};
+use sysinfo::{DiskExt, System, SystemExt};
 use toml_edit::{Array, RawString};
 
 static ENV_VAR_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$\{([A-Z0-9_]+)\}").unwrap());
@@ -295,6 +297,9 @@ pub fn set_proxy_variables(cmd: &mut Command) {
     if let Some(proxy) = config.http_proxy_url() {
         cmd.env("http_proxy", proxy);
     }
+    if let Some(no_proxy) = config.no_proxy() {
+        cmd.env("no_proxy", no_proxy);
+    }
 }
 
 /// Given a virtualenv returns the path to the python interpreter.
@@ -334,18 +339,40 @@ pub fn success_status() -> ExitStatus {
     }
 }
 
+/// Computes the hex encoded digest of a bytes slice.
+pub fn compute_checksum(content: &[u8], algorithm: &str) -> Result<String, Error> {
+    Ok(match algorithm {
+        "sha256" => hex::encode(Sha256::digest(content)),
+        "sha512" => hex::encode(Sha512::digest(content)),
+        other => bail!("unsupported checksum algorithm '{}'", other),
+    })
+}
+
 /// Takes a bytes slice and compares it to a given string checksum.
+///
+/// The checksum can be prefixed with the algorithm (`sha256:` or `sha512:`).
+/// Checksums without a prefix are sha256 checksums.
 pub fn check_checksum(content: &[u8], checksum: &str) -> Result<(), Error> {
-    let mut hasher = Sha256::new();
-    hasher.update(content);
-    let digest = hasher.finalize();
-    let digest = hex::encode(digest);
+    let (algorithm, checksum) = checksum.split_once(':').unwrap_or(("sha256", checksum));
+    let digest = compute_checksum(content, algorithm)?;
     if !digest.eq_ignore_ascii_case(checksum) {
         bail!("hash mismatch: expected {} got {}", checksum, digest);
     }
     Ok(())
 }
 
+/// Verifies a minisign signature of a bytes slice against a public key.
+pub fn check_signature(content: &[u8], signature: &[u8], public_key: &str) -> Result<(), Error> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key)
+        .map_err(|err| anyhow!("invalid public key: {}", err))?;
+    let signature = minisign_verify::Signature::decode(&String::from_utf8_lossy(signature))
+        .map_err(|err| anyhow!("invalid signature: {}", err))?;
+    public_key
+        .verify(content, &signature, false)
+        .map_err(|err| anyhow!("signature mismatch: {}", err))?;
+    Ok(())
+}
+
 /// Reformats a TOML array to multi line while trying to
 /// preserve all comments and move them around.  This also makes
 /// the array to have a trailing comma.
@@ -419,12 +446,156 @@ pub struct CopyDirOptions {
     pub exclude: Vec<PathBuf>,
 }
 
+/// An advisory lock on the rye home folder.
+///
+/// The lock is an OS file lock (`flock` on unix, `LockFileEx` on Windows) on
+/// a file that holds the pid of the process that owns it.  The OS releases
+/// it when this is dropped or the process dies, so a lock is never stale.
+#[derive(Debug)]
+pub struct AppDirLock {
+    _file: fs::File,
+}
+
+const APP_DIR_LOCK_NAME: &str = ".rye-lock";
+
+/// Locks the rye home folder against concurrent `rye self` operations.
+///
+/// If another process holds the lock, this waits up to `wait` for it to be
+/// released.
+pub fn lock_app_dir(app_dir: &Path, wait: Duration) -> Result<AppDirLock, Error> {
+    fs::create_dir_all(app_dir)?;
+    let path = app_dir.join(APP_DIR_LOCK_NAME);
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        // the file is never removed, so that all processes lock the same file
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| {
+                Error::from(err).context(format!("could not create lock file {}", path.display()))
+            })?;
+        if try_lock_file(&file)? {
+            file.set_len(0)?;
+            write!(file, "{}", std::process::id())?;
+            return Ok(AppDirLock { _file: file });
+        }
+
+        if started.elapsed() < wait {
+            if !waiting {
+                elog!("waiting for another rye operation to finish...");
+                waiting = true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+        // the owner may not have written its pid yet, and on Windows the
+        // locked file cannot be read at all.
+        match fs::read_to_string(&path)
+            .ok()
+            .and_then(|x| x.trim().parse::<u32>().ok())
+        {
+            Some(pid) => bail!("another rye operation is in progress (pid {})", pid),
+            None => bail!("another rye operation is in progress"),
+        }
+    }
+}
+
+/// Takes an exclusive lock on a file without blocking.
+///
+/// Returns `false` if another process holds the lock.
+#[cfg(unix)]
+fn try_lock_file(file: &fs::File) -> Result<bool, Error> {
+    use nix::errno::Errno;
+    use nix::fcntl::{flock, FlockArg};
+    use std::os::unix::io::AsRawFd;
+    match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+        Ok(()) => Ok(true),
+        Err(Errno::EWOULDBLOCK) => Ok(false),
+        Err(err) => Err(Error::from(err).context("could not lock the rye home folder")),
+    }
+}
+
+/// Takes an exclusive lock on a file without blocking.
+///
+/// Returns `false` if another process holds the lock.
+#[cfg(windows)]
+fn try_lock_file(file: &fs::File) -> Result<bool, Error> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    let rv = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            !0,
+            !0,
+            &mut overlapped,
+        )
+    };
+    if rv != 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(Error::from(err).context("could not lock the rye home folder"))
+    }
+}
+
+/// Returns the mount point of the disk a path is on and the space available on it.
+///
+/// The path does not have to exist yet, the closest existing parent is used.
+pub fn get_available_space(path: &Path) -> Option<(PathBuf, u64)> {
+    let path = path.ancestors().find_map(|x| x.canonicalize().ok())?;
+    let mut system = System::new();
+    system.refresh_disks_list();
+    system
+        .disks()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.available_space()))
+}
+
 #[test]
 fn test_quiet_exit_display() {
     let quiet_exit = QuietExit(0);
     assert_eq!("exit with 0", format!("{}", quiet_exit));
 }
 
+#[cfg(test)]
+mod test_check_checksum {
+    use super::check_checksum;
+
+    #[test]
+    fn test_check_checksum_sha256() {
+        let checksum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(check_checksum(b"hello", checksum).is_ok());
+        assert!(check_checksum(b"hello", &format!("sha256:{}", checksum)).is_ok());
+        assert!(check_checksum(b"world", checksum).is_err());
+    }
+
+    #[test]
+    fn test_check_checksum_sha512() {
+        let checksum = "sha512:9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7\
+                        2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043";
+        assert!(check_checksum(b"hello", checksum).is_ok());
+        assert!(check_checksum(b"world", checksum).is_err());
+    }
+
+    #[test]
+    fn test_check_checksum_unknown_algorithm() {
+        assert!(check_checksum(b"hello", "md5:5d41402abc4b2a76b9719d911017c592").is_err());
+    }
+}
+
 #[cfg(test)]
 mod test_format_requirement {
     use super::{format_requirement, Requirement};
@@ -530,3 +701,29 @@ mod test_is_inside_git_work_tree {
         assert!(!is_inside_git_work_tree(&PathBuf::from("/")));
     }
 }
+
+#[cfg(test)]
+mod test_lock_app_dir {
+    use super::{lock_app_dir, APP_DIR_LOCK_NAME};
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lock_app_dir_exclusive() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let lock = lock_app_dir(app_dir.path(), Duration::ZERO).unwrap();
+        let err = lock_app_dir(app_dir.path(), Duration::ZERO).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("another rye operation is in progress"));
+        drop(lock);
+        assert!(lock_app_dir(app_dir.path(), Duration::ZERO).is_ok());
+    }
+
+    #[test]
+    fn test_lock_app_dir_stale() {
+        let app_dir = tempfile::tempdir().unwrap();
+        fs::write(app_dir.path().join(APP_DIR_LOCK_NAME), u32::MAX.to_string()).unwrap();
+        assert!(lock_app_dir(app_dir.path(), Duration::ZERO).is_ok());
+    }
+}